@@ -1,58 +1,150 @@
-use super::hub::Hub;
-use crate::layers::{PhysicalLayer, NIC};
+use super::{
+    hub::Hub,
+    device_name,
+    ports::Ports,
+    segment::{self, Segment},
+};
+use crate::layers::{LinkProperties, PhysicalError, PhysicalLayer, NIC};
 use crate::utils::Simulateable;
 use futures::future::join_all;
 use std::sync::Arc;
+use tracing::instrument;
 
-const N_JUNC: usize = 5;
+/// Taps of each populated segment of a bus created with `Bus::default`
+const DEFAULT_TAPS: usize = 16;
 
+/// Length of a thick coax segment in metres
+const DEFAULT_LENGTH: f64 = 500.0;
+
+/// Segments of coax joined by repeaters.
+///
+/// Every segment is a junction hub with a port per tap, plus the trunk ports that link it to its
+/// neighbours. The trunks are links of the bus's properties, delayed by the length of the segments
+/// they join, so stations have to attach with links of the same mode.
 pub struct Bus {
-    junctions: [Arc<Hub>; N_JUNC],
+    name: String,
+    junctions: Vec<Arc<Hub>>,
+    segments: Vec<Segment>,
 }
 
 impl Default for Bus {
+    /// The largest bus the 5-4-3 rule allows: three populated segments joined by two link segments
     fn default() -> Self {
-        let junctions: [Arc<Hub>; N_JUNC] = Default::default();
-        for i in 1..N_JUNC {
-            junctions[i].connect(junctions[i - 1].clone());
-        }
-
-        Bus { junctions }
+        let populated = Segment::new(DEFAULT_TAPS, DEFAULT_LENGTH);
+        let link = Segment::link(DEFAULT_LENGTH);
+        Bus::new([populated, link, populated, link, populated]).expect("the default bus has taps")
     }
 }
 
 impl Bus {
-    pub fn index(number: usize) -> (usize, usize) {
-        (number >> 16, number & 0xFFFF)
+    /// Chains the segments in a line, with a warning for every rule the chain breaks
+    pub fn new(segments: impl IntoIterator<Item = Segment>) -> Result<Self, PhysicalError> {
+        Bus::with_properties(segments, LinkProperties::default())
     }
 
-    fn available_interface(&self) -> Option<usize> {
-        for (i, junction) in self.junctions.iter().enumerate() {
-            if let Some(interface) = junction.available_interface() {
-                return Some(i << 16 | interface);
-            }
+    /// Chains the segments with trunks of the given properties, at least one segment needs taps
+    pub fn with_properties(
+        segments: impl IntoIterator<Item = Segment>,
+        properties: LinkProperties,
+    ) -> Result<Self, PhysicalError> {
+        let segments: Vec<Segment> = segments.into_iter().collect();
+        if segments.iter().all(|segment| segment.taps == 0) {
+            return Err(PhysicalError::NoPorts);
+        }
+        let name = device_name("bus");
+        for violation in segment::validate(&segments) {
+            tracing::warn!(device = %name, ?violation, "bus breaks collision detection");
+        }
+
+        let last = segments.len().saturating_sub(1);
+        let junctions: Vec<Arc<Hub>> = segments
+            .iter()
+            .enumerate()
+            .map(|(i, segment)| {
+                let trunks = (i > 0) as usize + (i < last) as usize;
+                Hub::new(segment.taps + trunks).map(Arc::new)
+            })
+            .collect::<Result<_, _>>()?;
+        for i in 1..junctions.len() {
+            let delay = segment::trunk_delay(&segments[i - 1], &segments[i]);
+            junctions[i]
+                .connect_with(junctions[i - 1].clone(), LinkProperties { delay, ..properties })
+                .expect("a new hub has free trunk ports");
         }
 
-        None
+        Ok(Bus {
+            name,
+            junctions,
+            segments,
+        })
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The hubs the bus is made of, chained in a line
+    pub fn junctions(&self) -> &[Arc<Hub>] {
+        &self.junctions
+    }
+
+    /// Whether a port of a junction links it to its neighbour
+    ///
+    /// Every junction is connected to the previous one on its first port, which takes the first
+    /// free port of the previous junction.
+    fn is_trunk(&self, junction: usize, port: usize) -> bool {
+        let to_previous = junction > 0 && port == 0;
+        let to_next = junction + 1 < self.junctions.len() && port == (junction > 0) as usize;
+        to_previous || to_next
+    }
+
+    /// Junction and port of every tap, taps are numbered along the bus without the trunk ports
+    fn taps(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.junctions.iter().enumerate().flat_map(move |(junction, hub)| {
+            (0..hub.port_count())
+                .filter(move |&port| !self.is_trunk(junction, port))
+                .map(move |port| (junction, port))
+        })
+    }
+}
+
+impl Ports for Bus {
+    fn port_count(&self) -> usize {
+        self.taps().count()
+    }
+
+    fn port_nic(&self, tap: usize) -> Option<&NIC> {
+        let (junction, port) = self.taps().nth(tap)?;
+        self.junctions[junction].port_nic(port)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
     }
 }
 
 impl PhysicalLayer for Bus {
+    /// The first tap, a bus has no NIC of its own
     fn nic(&self) -> &NIC {
-        if let Some(interface) = self.available_interface() {
-            let (junction, iface) = Bus::index(interface);
-            &self.junctions[junction].interface(iface)
-        } else {
-            panic!("No NIC available")
-        }
+        self.port_nic(0).expect("a bus has taps")
     }
 
-    async fn disconnect(&self) {
-        println!("Disconnect is not implemented for Bus");
+    fn port(&self) -> Result<&NIC, PhysicalError> {
+        self.free_port()
+    }
+
+    fn accepts(&self, properties: &LinkProperties) -> Result<(), PhysicalError> {
+        self.junctions.iter().try_for_each(|junction| junction.accepts(properties))
+    }
+
+    /// Disconnects every station, the junctions stay chained
+    async fn disconnect(&self) -> Result<(), PhysicalError> {
+        self.disconnect_ports()
     }
 }
 
 impl Simulateable for Bus {
+    #[instrument(name = "bus", skip_all, fields(device = %self.name))]
     async fn tick(&self) {
         join_all(self.junctions.iter().map(|j| j.tick())).await;
     }
@@ -61,6 +153,7 @@ impl Simulateable for Bus {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tokio::time::Duration;
 
     struct TestDevice {
         nic: NIC,
@@ -79,13 +172,13 @@ mod tests {
         }
     }
 
-    #[tokio::test(flavor = "multi_thread")]
+    #[tokio::test]
     async fn test_bus() {
         let bus = Arc::new(Bus::default());
         let devices: [Arc<TestDevice>; 32] = Default::default();
 
         for device in &devices {
-            bus.connect(device.clone());
+            bus.connect(device.clone()).unwrap();
         }
 
         devices[0].transmit(0x09).await;
@@ -95,4 +188,90 @@ mod tests {
 
         assert_eq!(devices[31].receive().await, Some(0x09));
     }
+
+    #[tokio::test]
+    async fn test_bus_disconnect() {
+        let bus = Arc::new(Bus::default());
+        let devices: [Arc<TestDevice>; 2] = Default::default();
+        for device in &devices {
+            bus.connect(device.clone()).unwrap();
+        }
+
+        assert_eq!(bus.disconnect().await, Ok(()));
+        assert_eq!(bus.disconnect().await, Err(PhysicalError::LinkDown));
+        let n = bus.junctions.len();
+        for i in 0..n - 1 {
+            assert!(bus.is_trunk(i + 1, 0));
+            assert!(bus.junctions[i + 1].interface(0).unwrap().is_connected());
+        }
+        assert_eq!(bus.junctions.iter().flat_map(|j| j.interfaces()).filter(|i| i.is_connected()).count(), 2 * (n - 1));
+    }
+
+    #[tokio::test]
+    async fn test_taps() {
+        let bus = Arc::new(Bus::default());
+        // Only the populated segments have taps
+        assert_eq!(bus.port_count(), 3 * DEFAULT_TAPS);
+
+        let dev1 = Arc::new(TestDevice::default());
+        let dev2 = Arc::new(TestDevice::default());
+        let last = bus.port_count() - 1;
+        bus.connect_port(0, dev1.clone()).unwrap();
+        bus.connect_port(last, dev2.clone()).unwrap();
+        assert_eq!(bus.attachments(), vec![(0, dev1.nic().mac()), (last, dev2.nic().mac())]);
+
+        dev1.transmit(0x09).await;
+        bus.tick().await;
+        assert_eq!(dev2.receive().await, Some(0x09));
+
+        bus.disconnect_port(last).unwrap();
+        assert!(!dev2.nic().is_connected());
+        assert_eq!(bus.attachments(), vec![(0, dev1.nic().mac())]);
+    }
+
+    #[test]
+    fn test_segments() {
+        let bus = Bus::new([Segment::new(2, 100.0), Segment::new(3, 185.0)]).unwrap();
+        assert_eq!(bus.nic().mac(), bus.port_nic(0).unwrap().mac());
+        assert_ne!(bus.nic().mac(), bus.junctions[0].interface(0).unwrap().mac());
+        assert_eq!(bus.port_count(), 5);
+        assert_eq!(bus.junctions[0].port_count(), 3);
+
+        let devices: [Arc<TestDevice>; 5] = Default::default();
+        for device in &devices {
+            bus.connect(device.clone()).unwrap();
+        }
+        assert_eq!(bus.connect(Arc::new(TestDevice::default())), Err(PhysicalError::NoFreePort));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_frame_mode() {
+        let properties = LinkProperties::frames(8_000_000, Duration::ZERO);
+        let segments = [Segment::new(2, 100.0), Segment::new(2, 100.0)];
+        let bus = Arc::new(Bus::with_properties(segments, properties).unwrap());
+        let dev1 = Arc::new(TestDevice::default());
+        let dev2 = Arc::new(TestDevice::default());
+
+        // The trunks carry frames, so bytes could not cross the bus
+        assert_eq!(bus.connect(dev1.clone()), Err(PhysicalError::ModeMismatch));
+        assert_eq!(bus.connect_port(0, dev1.clone()), Err(PhysicalError::ModeMismatch));
+        bus.connect_port_with(0, dev1.clone(), properties).unwrap();
+        bus.connect_port_with(3, dev2.clone(), properties).unwrap();
+
+        // The trunk delays the frame by the length of the segments
+        let hop = properties.transmission_time(64) + segment::trunk_delay(&segments[0], &segments[1]);
+        dev1.nic().transmit_frame(vec![0x09; 64]).await.unwrap();
+        tokio::time::sleep(properties.transmission_time(64)).await;
+        bus.tick().await;
+        tokio::time::sleep(hop).await;
+        bus.tick().await;
+        tokio::time::sleep(properties.transmission_time(64)).await;
+        assert_eq!(dev2.nic().recieve_frame().await, Some(vec![0x09; 64]));
+    }
+
+    #[test]
+    fn test_no_taps() {
+        assert!(matches!(Bus::new([]), Err(PhysicalError::NoPorts)));
+        assert!(matches!(Bus::new([Segment::link(100.0)]), Err(PhysicalError::NoPorts)));
+    }
 }
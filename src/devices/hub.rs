@@ -1,50 +1,79 @@
-use crate::layers::{PhysicalLayer, NIC};
+use super::{device_name, ports::Ports};
+use crate::layers::{Duplex, DuplexSetting, LinkProperties, PhysicalError, PhysicalLayer, NIC};
 use crate::utils::Simulateable;
 use std::sync::Arc;
+use tracing::instrument;
+
+/// Number of ports of a hub created with `Hub::default`
+const DEFAULT_PORTS: usize = 8;
 
 pub struct Hub {
-    interfaces: [Arc<NIC>; 8],
+    name: String,
+    interfaces: Vec<Arc<NIC>>,
 }
 
 impl PhysicalLayer for Hub {
+    /// The first port, a hub has no NIC of its own
     fn nic(&self) -> &NIC {
-        if let Some(interface) = self.available_interface() {
-            &self.interfaces[interface]
-        } else {
-            panic!("No NIC available")
+        &self.interfaces[0]
+    }
+
+    fn port(&self) -> Result<&NIC, PhysicalError> {
+        self.free_port()
+    }
+
+    /// Bytes and frames are only repeated between ports of the same mode
+    fn accepts(&self, properties: &LinkProperties) -> Result<(), PhysicalError> {
+        let mut connected = self.interfaces().filter(|iface| iface.is_connected());
+        match connected.all(|iface| iface.mode() == properties.mode) {
+            true => Ok(()),
+            false => Err(PhysicalError::ModeMismatch),
         }
     }
 
-    async fn disconnect(&self) {
-        unimplemented!("Hub does not have a NIC")
+    /// Disconnects every port
+    async fn disconnect(&self) -> Result<(), PhysicalError> {
+        self.disconnect_ports()
     }
 }
 
 impl Hub {
-    pub fn available_interface(&self) -> Option<usize> {
-        for (i, iface) in self.interfaces.iter().enumerate() {
-            if !iface.is_connected() {
-                return Some(i);
-            }
+    /// Hubs repeat onto a shared medium, so their ports only operate in half duplex
+    pub fn new(ports: usize) -> Result<Self, PhysicalError> {
+        if ports == 0 {
+            return Err(PhysicalError::NoPorts);
         }
+        Ok(Hub {
+            name: device_name("hub"),
+            interfaces: (0..ports)
+                .map(|_| Arc::new(NIC::with_duplex(DuplexSetting::Forced(Duplex::Half))))
+                .collect(),
+        })
+    }
+}
 
-        None
+impl Ports for Hub {
+    fn port_count(&self) -> usize {
+        self.interfaces.len()
     }
 
-    pub fn interface(&self, index: usize) -> &NIC {
-        &self.interfaces[index]
+    fn port_nic(&self, port: usize) -> Option<&NIC> {
+        self.interfaces.get(port).map(|iface| iface.as_ref())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
     }
 }
 
 impl Default for Hub {
     fn default() -> Self {
-        Hub {
-            interfaces: Default::default(),
-        }
+        Hub::new(DEFAULT_PORTS).expect("the default hub has ports")
     }
 }
 
 impl Simulateable for Hub {
+    #[instrument(name = "hub", skip_all, fields(device = %self.name))]
     async fn tick(&self) {
         let mut connected_ifaces = Vec::new();
         for iface in self.interfaces.iter() {
@@ -53,16 +82,33 @@ impl Simulateable for Hub {
             }
         }
 
-        let mut bytes = Vec::new();
-        for iface in connected_ifaces.iter() {
-            if let Some(byte) = iface.recieve().await {
-                bytes.push(byte);
+        let mut symbols = Vec::new();
+        for (from, iface) in connected_ifaces.iter().enumerate() {
+            if let Some(symbol) = iface.recieve_symbol().await {
+                symbols.push((from, symbol));
+            }
+        }
+        if symbols.len() > 1 {
+            tracing::trace!(ports = symbols.len(), "collision on hub");
+        }
+
+        // A station does not hear its own bytes, it senses a collision when another port sends
+        for (to, iface) in connected_ifaces.iter().enumerate() {
+            for (from, symbol) in symbols.iter() {
+                if *from != to {
+                    iface.transmit_symbol(*symbol).await;
+                }
             }
         }
 
-        for iface in connected_ifaces.iter() {
-            for byte in bytes.iter() {
-                iface.transmit(*byte).await;
+        // Frames on frame mode links are repeated to every other port
+        for (from, iface) in connected_ifaces.iter().enumerate() {
+            while let Some(frame) = iface.recieve_frame().await {
+                for (to, other) in connected_ifaces.iter().enumerate() {
+                    if to != from {
+                        other.relay_frame(frame.clone()).await;
+                    }
+                }
             }
         }
     }
@@ -71,6 +117,11 @@ impl Simulateable for Hub {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::layers::LinkProperties;
+    use crate::tui::EventLog;
+    use crate::utils::clock;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tokio::time::Duration;
 
     struct TestDevice {
         nic: NIC,
@@ -89,17 +140,140 @@ mod tests {
         }
     }
 
-    #[tokio::test(flavor = "multi_thread")]
+    #[tokio::test]
     async fn test_hub() {
         let hub = Arc::new(Hub::default());
         let dev1 = Arc::new(TestDevice::default());
         let dev2 = Arc::new(TestDevice::default());
 
-        dev1.connect(hub.clone());
-        hub.connect(dev2.clone());
+        dev1.connect(hub.clone()).unwrap();
+        hub.connect(dev2.clone()).unwrap();
+
+        assert_eq!(dev1.nic().duplex(), Duplex::Half);
+        assert_eq!(dev2.nic().duplex(), Duplex::Half);
 
         dev1.transmit(0x09).await;
         hub.tick().await;
         assert_eq!(dev2.receive().await, Some(0x09));
     }
+
+    #[tokio::test]
+    async fn test_hub_frame_mode() {
+        let hub = Arc::new(Hub::default());
+        let dev1 = Arc::new(TestDevice::default());
+        let dev2 = Arc::new(TestDevice::default());
+        let properties = LinkProperties::frames(8_000_000, Duration::ZERO);
+        dev1.connect_with(hub.clone(), properties).unwrap();
+        dev2.connect_with(hub.clone(), properties).unwrap();
+        let dev3 = Arc::new(TestDevice::default());
+        assert_eq!(dev3.connect(hub.clone()), Err(PhysicalError::ModeMismatch));
+        let stalled = LinkProperties::frames(0, Duration::ZERO);
+        assert_eq!(dev3.connect_with(hub.clone(), stalled), Err(PhysicalError::ZeroBandwidth));
+
+        let arrival = dev1.nic().transmit_frame(vec![0x09; 64]).await.unwrap();
+        tokio::time::sleep(arrival.saturating_sub(clock::now())).await;
+        hub.tick().await;
+        tokio::time::sleep(properties.transmission_time(64)).await;
+
+        assert_eq!(dev2.nic().recieve_frame().await, Some(vec![0x09; 64]));
+        assert_eq!(dev1.nic().recieve_frame().await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hub_frame_dropped() {
+        let hub = Arc::new(Hub::default());
+        let devices: [Arc<TestDevice>; 3] = Default::default();
+        let properties = LinkProperties::frames(8_000_000, Duration::ZERO);
+        for device in &devices {
+            device.connect_with(hub.clone(), properties).unwrap();
+        }
+
+        // Two senders fill the link to the third port faster than it can take the frames
+        for _ in 0..40 {
+            devices[0].nic().transmit_frame(vec![0x09; 64]).await.unwrap();
+            devices[1].nic().transmit_frame(vec![0x0A; 64]).await.unwrap();
+        }
+        tokio::time::sleep(properties.transmission_time(40 * 64)).await;
+        hub.tick().await;
+
+        let dropped = hub.interface(2).unwrap().statistics().frames_dropped.get();
+        assert_eq!(dropped, 80 - 64);
+    }
+
+    #[test]
+    fn test_no_ports() {
+        assert!(matches!(Hub::new(0), Err(PhysicalError::NoPorts)));
+    }
+
+    #[tokio::test]
+    async fn test_device_events() {
+        let log = EventLog::default();
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(log.clone()));
+        let hub = Arc::new(Hub::default());
+        let devices: [Arc<TestDevice>; 2] = Default::default();
+        for device in &devices {
+            device.connect(hub.clone()).unwrap();
+        }
+        devices[0].transmit(0x09).await;
+        devices[1].transmit(0x0a).await;
+        hub.tick().await;
+        hub.disconnect_port(0).unwrap();
+
+        let device = format!("device={}", hub.name());
+        let lines = log.tail(10);
+        assert!(lines.iter().any(|line| line.contains("carrier up")));
+        assert!(lines.iter().any(|line| line.contains(&format!("hub{{{device}}}: collision on hub"))));
+        assert!(lines.iter().any(|line| line.contains("port unplugged") && line.contains(&device)));
+    }
+
+    #[test]
+    fn test_no_such_port() {
+        let hub = Hub::default();
+        assert_eq!(hub.interface(8).err(), Some(PhysicalError::NoSuchPort(8)));
+        assert!(hub.interface(7).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_no_free_port() {
+        let hub = Arc::new(Hub::default());
+        let devices: [Arc<TestDevice>; 8] = Default::default();
+        for device in &devices {
+            device.connect(hub.clone()).unwrap();
+        }
+        let extra = Arc::new(TestDevice::default());
+        assert_eq!(extra.connect(hub.clone()), Err(PhysicalError::NoFreePort));
+
+        assert_eq!(hub.disconnect().await, Ok(()));
+        assert_eq!(hub.disconnect().await, Err(PhysicalError::LinkDown));
+        assert_eq!(extra.connect(hub.clone()), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_ports() {
+        let hub = Arc::new(Hub::default());
+        let dev1 = Arc::new(TestDevice::default());
+        let dev2 = Arc::new(TestDevice::default());
+
+        hub.connect_port(3, dev1.clone()).unwrap();
+        assert_eq!(hub.connect_port(3, dev2.clone()), Err(PhysicalError::PortInUse(3)));
+        assert_eq!(hub.connect_port(8, dev2.clone()), Err(PhysicalError::NoSuchPort(8)));
+        hub.connect_port(5, dev2.clone()).unwrap();
+        assert_eq!(hub.attachments(), vec![(3, dev1.nic().mac()), (5, dev2.nic().mac())]);
+        assert_eq!(dev1.nic().partner(), Some(hub.interface(3).unwrap().mac()));
+
+        // Unplugging drops the carrier at the device as well
+        hub.disconnect_port(3).unwrap();
+        assert!(!dev1.nic().is_connected());
+        assert_eq!(hub.attached(3), None);
+        assert_eq!(hub.disconnect_port(3), Err(PhysicalError::LinkDown));
+
+        // Plugged in again between ticks of a running hub
+        dev2.transmit(0x01).await;
+        hub.tick().await;
+        hub.connect_port(0, dev1.clone()).unwrap();
+        dev2.transmit(0x02).await;
+        hub.tick().await;
+        assert_eq!(dev1.receive().await, Some(0x02));
+    }
 }
+
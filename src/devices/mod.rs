@@ -1,2 +1,17 @@
 pub mod hub;
 pub mod bus;
+pub mod ports;
+pub mod ring;
+pub mod segment;
+pub mod topology;
+pub mod wireless;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Devices created so far, numbers the names that tell them apart in events
+static DEVICES: AtomicUsize = AtomicUsize::new(0);
+
+/// A name for a new device of the given kind, e.g. `hub0`
+fn device_name(kind: &str) -> String {
+    format!("{kind}{}", DEVICES.fetch_add(1, Ordering::Relaxed))
+}
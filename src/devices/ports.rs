@@ -0,0 +1,90 @@
+use crate::layers::{attach, LinkProperties, MacAddr, PhysicalError, PhysicalLayer, NIC};
+use std::sync::Arc;
+
+/// A device with numbered ports, that devices can be plugged into and unplugged from.
+///
+/// Ports can be changed while the simulation runs. Unplugging a port drops its link, so the
+/// device at the other end loses the carrier as well.
+pub trait Ports: PhysicalLayer {
+    fn port_count(&self) -> usize;
+
+    /// The NIC behind a port
+    fn port_nic(&self, port: usize) -> Option<&NIC>;
+
+    /// Name of the device in events
+    fn name(&self) -> &str;
+
+    fn interface(&self, port: usize) -> Result<&NIC, PhysicalError> {
+        self.port_nic(port).ok_or(PhysicalError::NoSuchPort(port))
+    }
+
+    fn interfaces(&self) -> impl Iterator<Item = &NIC> {
+        (0..self.port_count()).filter_map(|port| self.port_nic(port))
+    }
+
+    /// The first port nothing is plugged into
+    fn available_interface(&self) -> Option<usize> {
+        self.interfaces().position(|iface| !iface.is_connected())
+    }
+
+    /// The NIC of the first free port, for `PhysicalLayer::port`
+    fn free_port(&self) -> Result<&NIC, PhysicalError> {
+        let port = self.available_interface().ok_or(PhysicalError::NoFreePort)?;
+        self.interface(port)
+    }
+
+    /// Unplugs every port, for `PhysicalLayer::disconnect`
+    fn disconnect_ports(&self) -> Result<(), PhysicalError> {
+        let mut disconnected = false;
+        for port in 0..self.port_count() {
+            disconnected |= self.disconnect_port(port).is_ok();
+        }
+        match disconnected {
+            true => Ok(()),
+            false => Err(PhysicalError::LinkDown),
+        }
+    }
+
+    /// Plugs a device into a specific port
+    fn connect_port(&self, port: usize, device: Arc<impl PhysicalLayer>) -> Result<(), PhysicalError> {
+        self.connect_port_with(port, device, LinkProperties::default())
+    }
+
+    fn connect_port_with(
+        &self,
+        port: usize,
+        device: Arc<impl PhysicalLayer>,
+        properties: LinkProperties,
+    ) -> Result<(), PhysicalError> {
+        let nic = self.interface(port)?;
+        if nic.is_connected() {
+            return Err(PhysicalError::PortInUse(port));
+        }
+        self.accepts(&properties)?;
+        device.accepts(&properties)?;
+        attach(nic, device.port()?, properties)
+    }
+
+    /// Unplugs whatever is connected to a port
+    fn disconnect_port(&self, port: usize) -> Result<(), PhysicalError> {
+        let nic = self.interface(port)?;
+        if !nic.is_connected() {
+            return Err(PhysicalError::LinkDown);
+        }
+        tracing::debug!(device = self.name(), port, partner = ?nic.partner().map(|mac| mac.to_string()), "port unplugged");
+        nic.set_connection(None);
+        Ok(())
+    }
+
+    /// MAC of the device plugged into a port
+    fn attached(&self, port: usize) -> Option<MacAddr> {
+        self.port_nic(port)?.partner()
+    }
+
+    /// The connected ports and the MAC of the device plugged into each
+    fn attachments(&self) -> Vec<(usize, MacAddr)> {
+        (0..self.port_count())
+            .filter_map(|port| Some((port, self.attached(port)?)))
+            .collect()
+    }
+}
@@ -0,0 +1,149 @@
+use super::{device_name, ports::Ports};
+use crate::layers::{PhysicalError, PhysicalLayer, NIC};
+use crate::utils::Simulateable;
+use std::sync::Arc;
+use tracing::instrument;
+
+/// Number of ports of a ring created with `Ring::default`
+const DEFAULT_PORTS: usize = 8;
+
+/// A ring of point to point links, as used by token ring networks.
+///
+/// Unlike a `Hub`, a byte sent by a station is only delivered to the next connected station
+/// downstream, which has to repeat it for the rest of the ring.
+pub struct Ring {
+    name: String,
+    interfaces: Vec<Arc<NIC>>,
+}
+
+impl Default for Ring {
+    fn default() -> Self {
+        Ring::new(DEFAULT_PORTS).expect("the default ring has ports")
+    }
+}
+
+impl PhysicalLayer for Ring {
+    /// The first port, a ring has no NIC of its own
+    fn nic(&self) -> &NIC {
+        &self.interfaces[0]
+    }
+
+    fn port(&self) -> Result<&NIC, PhysicalError> {
+        self.free_port()
+    }
+
+    /// Disconnects every port
+    async fn disconnect(&self) -> Result<(), PhysicalError> {
+        self.disconnect_ports()
+    }
+}
+
+impl Ports for Ring {
+    fn port_count(&self) -> usize {
+        self.interfaces.len()
+    }
+
+    fn port_nic(&self, port: usize) -> Option<&NIC> {
+        self.interfaces.get(port).map(|iface| iface.as_ref())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Ring {
+    pub fn new(ports: usize) -> Result<Self, PhysicalError> {
+        if ports == 0 {
+            return Err(PhysicalError::NoPorts);
+        }
+        Ok(Ring {
+            name: device_name("ring"),
+            interfaces: (0..ports).map(|_| Arc::new(NIC::default())).collect(),
+        })
+    }
+
+    /// The next connected interface downstream of `index`
+    pub fn downstream(&self, index: usize) -> Option<usize> {
+        let n = self.interfaces.len();
+        (1..n)
+            .map(|offset| (index + offset) % n)
+            .find(|&i| self.interfaces[i].is_connected())
+    }
+}
+
+impl Simulateable for Ring {
+    #[instrument(name = "ring", skip_all, fields(device = %self.name))]
+    async fn tick(&self) {
+        let mut sent = Vec::new();
+        for (i, iface) in self.interfaces.iter().enumerate() {
+            if iface.is_connected() {
+                if let Some(symbol) = iface.recieve_symbol().await {
+                    sent.push((i, symbol));
+                }
+            }
+        }
+
+        for (from, symbol) in sent {
+            if let Some(to) = self.downstream(from) {
+                self.interfaces[to].transmit_symbol(symbol).await;
+            }
+        }
+
+        for (from, iface) in self.interfaces.iter().enumerate() {
+            while let Some(frame) = iface.recieve_frame().await {
+                if let Some(to) = self.downstream(from) {
+                    self.interfaces[to].relay_frame(frame).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestDevice {
+        nic: NIC,
+    }
+
+    impl PhysicalLayer for TestDevice {
+        fn nic(&self) -> &NIC {
+            &self.nic
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ring() {
+        let ring = Arc::new(Ring::default());
+        let devices: [Arc<TestDevice>; 3] = Default::default();
+        for device in &devices {
+            ring.connect(device.clone()).unwrap();
+        }
+
+        devices[2].transmit(0x09).await;
+        ring.tick().await;
+        assert_eq!(devices[0].receive().await, Some(0x09));
+        assert_eq!(devices[1].receive().await, None);
+
+        devices[1].transmit(0x0a).await;
+        ring.tick().await;
+        assert_eq!(devices[2].receive().await, Some(0x0a));
+    }
+
+    #[tokio::test]
+    async fn test_ring_ports() {
+        assert!(matches!(Ring::new(0), Err(PhysicalError::NoPorts)));
+
+        let ring = Arc::new(Ring::new(3).unwrap());
+        let devices: [Arc<TestDevice>; 3] = Default::default();
+        for device in &devices {
+            ring.connect(device.clone()).unwrap();
+        }
+        let extra = Arc::new(TestDevice::default());
+        assert_eq!(extra.connect(ring.clone()), Err(PhysicalError::NoFreePort));
+        assert_eq!(ring.downstream(2), Some(0));
+    }
+}
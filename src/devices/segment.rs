@@ -0,0 +1,125 @@
+/*
+  Reference:
+    IEEE 802.3, Clause 13 (System considerations for multisegment 10 Mb/s baseband networks)
+*/
+
+use crate::layers::SLOT_SIZE;
+use crate::utils::clock;
+use tokio::time::Duration;
+
+/// A collision must reach the sender within the slot of the MAC, in bit times
+const SLOT_TIME: f64 = (SLOT_SIZE * 8) as f64;
+
+/// Propagation delay of coax in bit times per metre, at 0.77 c and 10 Mb/s
+const COAX_DELAY: f64 = 0.0433;
+
+/// Delay of a repeater in bit times, start-up included
+const REPEATER_DELAY: f64 = 8.0;
+
+// The 5-4-3 rule
+const MAX_SEGMENTS: usize = 5;
+const MAX_REPEATERS: usize = 4;
+const MAX_POPULATED: usize = 3;
+
+/// A segment of a bus, joined to its neighbours by repeaters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    /// Number of stations that can be attached, a link segment has none
+    pub taps: usize,
+    /// Length in metres
+    pub length: f64,
+}
+
+impl Segment {
+    pub fn new(taps: usize, length: f64) -> Self {
+        Segment { taps, length }
+    }
+
+    /// A segment that only joins two repeaters
+    pub fn link(length: f64) -> Self {
+        Segment { taps: 0, length }
+    }
+
+    /// Propagation delay from one end of the segment to the other, in bit times
+    fn propagation(&self) -> f64 {
+        self.length * COAX_DELAY
+    }
+}
+
+/// Delay of the trunk joining two segments: from the middle of one, through the repeater, to the
+/// middle of the other
+pub fn trunk_delay(from: &Segment, to: &Segment) -> Duration {
+    let bit_times = (from.propagation() + to.propagation()) / 2.0 + REPEATER_DELAY;
+    clock::BYTE_TIME.mul_f64(bit_times / 8.0)
+}
+
+/// Ways a chain of segments breaks the rules that keep collisions detectable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    TooManySegments(usize),
+    TooManyRepeaters(usize),
+    /// More segments with stations on them than the rule allows
+    TooManyPopulated(usize),
+    /// The round trip delay in bit times exceeds the slot time, collisions at the far end go unnoticed
+    DiameterExceeded(f64),
+}
+
+/// Round trip delay in bit times between stations at both ends of a chain of segments
+pub fn round_trip_delay(segments: &[Segment]) -> f64 {
+    let propagation: f64 = segments.iter().map(Segment::propagation).sum();
+    let repeaters = segments.len().saturating_sub(1) as f64;
+    2.0 * (propagation + repeaters * REPEATER_DELAY)
+}
+
+/// Checks a chain of segments against the 5-4-3 rule and the slot time
+pub fn validate(segments: &[Segment]) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let repeaters = segments.len().saturating_sub(1);
+    let populated = segments.iter().filter(|segment| segment.taps > 0).count();
+    if segments.len() > MAX_SEGMENTS {
+        violations.push(Violation::TooManySegments(segments.len()));
+    }
+    if repeaters > MAX_REPEATERS {
+        violations.push(Violation::TooManyRepeaters(repeaters));
+    }
+    if populated > MAX_POPULATED {
+        violations.push(Violation::TooManyPopulated(populated));
+    }
+    let round_trip = round_trip_delay(segments);
+    if round_trip > SLOT_TIME {
+        violations.push(Violation::DiameterExceeded(round_trip));
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let thick = Segment::new(100, 500.0);
+        let compliant = [thick, Segment::link(500.0), thick, Segment::link(500.0), thick];
+        assert_eq!(validate(&compliant), vec![]);
+
+        assert_eq!(
+            validate(&[thick; 6]),
+            vec![
+                Violation::TooManySegments(6),
+                Violation::TooManyRepeaters(5),
+                Violation::TooManyPopulated(6)
+            ]
+        );
+
+        let long = [Segment::new(10, 50_000.0), Segment::new(10, 50_000.0)];
+        assert!(matches!(validate(&long)[..], [Violation::DiameterExceeded(delay)] if delay > SLOT_TIME));
+    }
+
+    #[test]
+    fn test_trunk_delay() {
+        let short = Segment::new(2, 100.0);
+        let long = Segment::new(2, 1000.0);
+        assert!(trunk_delay(&short, &short) < trunk_delay(&short, &long));
+        assert_eq!(trunk_delay(&Segment::link(0.0), &Segment::link(0.0)), clock::BYTE_TIME);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::layers::{LinkProperties, NIC};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// A named set of devices whose connections can be exported as a Graphviz graph.
+///
+/// Devices are registered with the NICs of their ports, connections are discovered from the
+/// links the NICs hold at the time of the export.
+#[derive(Default)]
+pub struct Topology<'a> {
+    nodes: Vec<(String, Vec<&'a NIC>)>,
+}
+
+/// One end of a connection between registered ports.
+pub struct Endpoint<'a> {
+    pub node: &'a str,
+    pub port: usize,
+    pub nic: &'a NIC,
+    pub up: bool,
+    pub properties: LinkProperties,
+}
+
+pub fn format_bandwidth(bits: u64) -> String {
+    const UNITS: [&str; 4] = ["bit/s", "kbit/s", "Mbit/s", "Gbit/s"];
+    let mut value = bits as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{} {}", value, UNITS[unit])
+}
+
+/// Escapes a name for use in a quoted DOT string
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl<'a> Topology<'a> {
+    /// Registers a device, port numbers are the positions of the NICs in `ports`
+    pub fn add(&mut self, name: impl Into<String>, ports: impl IntoIterator<Item = &'a NIC>) {
+        self.nodes.push((name.into(), ports.into_iter().collect()));
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (&str, &[&'a NIC])> {
+        self.nodes.iter().map(|(name, ports)| (name.as_str(), ports.as_slice()))
+    }
+
+    /// The current connections by link id, with the registered ports at their ends.
+    ///
+    /// A connection with a single endpoint leads to a NIC that was not registered.
+    pub fn connections(&self) -> BTreeMap<usize, Vec<Endpoint<'_>>> {
+        let mut links: BTreeMap<usize, Vec<Endpoint>> = BTreeMap::new();
+        for (name, ports) in self.nodes.iter() {
+            for (port, nic) in ports.iter().enumerate() {
+                if let Some((id, up, properties)) = nic.with_link(|l| (l.id(), l.is_up(), l.properties())) {
+                    links.entry(id).or_default().push(Endpoint {
+                        node: name,
+                        port,
+                        nic,
+                        up,
+                        properties,
+                    });
+                }
+            }
+        }
+        links
+    }
+
+    /// Exports the current topology in the DOT language.
+    ///
+    /// Devices become record nodes with a field per port, connections become edges between the
+    /// ports labelled with bandwidth and delay. Links whose other end has gone are dashed, links
+    /// to a NIC that was not registered end in an anonymous point.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("graph topology {\n    node [shape=record];\n");
+
+        for (name, ports) in self.nodes.iter() {
+            let name = escape(name);
+            let fields: Vec<String> = (0..ports.len()).map(|i| format!("<p{i}> {i}")).collect();
+            writeln!(dot, "    \"{name}\" [label=\"{name}|{{{}}}\"];", fields.join("|")).unwrap();
+        }
+
+        for (id, endpoints) in self.connections().iter() {
+            let first = &endpoints[0];
+            let target = match endpoints.get(1) {
+                Some(other) => format!("\"{}\":p{}", escape(other.node), other.port),
+                None => {
+                    writeln!(dot, "    \"link{id}\" [shape=point];").unwrap();
+                    format!("\"link{id}\"")
+                }
+            };
+            let style = if endpoints.iter().all(|e| e.up) { "solid" } else { "dashed" };
+            writeln!(
+                dot,
+                "    \"{}\":p{} -- {} [label=\"{}, {:?}\", style={}];",
+                escape(first.node),
+                first.port,
+                target,
+                format_bandwidth(first.properties.bandwidth),
+                first.properties.delay,
+                style
+            )
+            .unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::{hub::Hub, ports::Ports};
+    use crate::layers::PhysicalLayer;
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct TestDevice {
+        nic: NIC,
+    }
+
+    impl PhysicalLayer for TestDevice {
+        fn nic(&self) -> &NIC {
+            &self.nic
+        }
+    }
+
+    #[tokio::test]
+    async fn test_to_dot() {
+        let hub = Arc::new(Hub::default());
+        let dev1 = Arc::new(TestDevice::default());
+        let dev2 = Arc::new(TestDevice::default());
+        let dev3 = Arc::new(TestDevice::default());
+        dev1.connect(hub.clone()).unwrap();
+        dev2.connect(hub.clone()).unwrap();
+        dev3.connect(hub.clone()).unwrap();
+
+        let mut topology = Topology::default();
+        topology.add("hub", hub.interfaces());
+        topology.add("dev1", [dev1.nic()]);
+        topology.add("dev2", [dev2.nic()]);
+
+        let dot = topology.to_dot();
+        assert!(dot.contains("\"hub\" [label=\"hub|{<p0> 0|<p1> 1|<p2> 2|"));
+        assert!(dot.contains("\"hub\":p0 -- \"dev1\":p0 [label=\"8 kbit/s, 0ns\", style=solid];"));
+        assert!(dot.contains("\"hub\":p1 -- \"dev2\":p0"));
+        assert!(dot.contains("\"hub\":p2 -- \"link"));
+    }
+
+    #[tokio::test]
+    async fn test_to_dot_escapes_names() {
+        let dev1 = Arc::new(TestDevice::default());
+        let dev2 = Arc::new(TestDevice::default());
+        dev1.connect(dev2.clone()).unwrap();
+
+        let mut topology = Topology::default();
+        topology.add("say \"hi\"", [dev1.nic()]);
+        topology.add("C:\\dev", [dev2.nic()]);
+
+        let dot = topology.to_dot();
+        assert!(dot.contains("\"say \\\"hi\\\"\" [label=\"say \\\"hi\\\"|{<p0> 0}\"];"));
+        assert!(dot.contains("\"say \\\"hi\\\"\":p0 -- \"C:\\\\dev\":p0"));
+    }
+}
@@ -0,0 +1,165 @@
+use super::{device_name, ports::Ports};
+use crate::layers::{Duplex, DuplexSetting, PhysicalError, PhysicalLayer, NIC};
+use crate::utils::Simulateable;
+use std::sync::Arc;
+use tracing::instrument;
+
+/// Position of a station on the plane in metres.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Position {
+    pub fn new(x: f64, y: f64) -> Self {
+        Position { x, y }
+    }
+
+    pub fn distance(&self, other: &Position) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+/// A shared radio medium.
+///
+/// Each station is attached through its own interface at a fixed position. A byte sent by a
+/// station is only heard by the stations within `range` of it, so two stations out of range of
+/// each other can both reach a third one without sensing each other (hidden terminals).
+/// A station never hears its own transmission, which is why collisions cannot be detected.
+pub struct Wireless {
+    name: String,
+    range: f64,
+    interfaces: Vec<Arc<NIC>>,
+    positions: Vec<Position>,
+}
+
+impl PhysicalLayer for Wireless {
+    /// The first port, the medium has no NIC of its own
+    fn nic(&self) -> &NIC {
+        &self.interfaces[0]
+    }
+
+    fn port(&self) -> Result<&NIC, PhysicalError> {
+        self.free_port()
+    }
+
+    /// Disconnects every port
+    async fn disconnect(&self) -> Result<(), PhysicalError> {
+        self.disconnect_ports()
+    }
+}
+
+impl Ports for Wireless {
+    fn port_count(&self) -> usize {
+        self.interfaces.len()
+    }
+
+    fn port_nic(&self, port: usize) -> Option<&NIC> {
+        self.interfaces.get(port).map(|iface| iface.as_ref())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Wireless {
+    /// Creates a medium with one interface per position, assigned to stations in connection order.
+    pub fn new(range: f64, positions: impl IntoIterator<Item = Position>) -> Result<Self, PhysicalError> {
+        let positions: Vec<Position> = positions.into_iter().collect();
+        if positions.is_empty() {
+            return Err(PhysicalError::NoPorts);
+        }
+        Ok(Wireless {
+            name: device_name("wireless"),
+            range,
+            interfaces: positions
+                .iter()
+                .map(|_| Arc::new(NIC::with_duplex(DuplexSetting::Forced(Duplex::Half))))
+                .collect(),
+            positions,
+        })
+    }
+
+    pub fn position(&self, port: usize) -> Result<Position, PhysicalError> {
+        self.positions.get(port).copied().ok_or(PhysicalError::NoSuchPort(port))
+    }
+
+    /// Whether a station on interface `to` hears a station on interface `from`
+    pub fn reachable(&self, from: usize, to: usize) -> bool {
+        from != to && self.positions[from].distance(&self.positions[to]) <= self.range
+    }
+}
+
+impl Simulateable for Wireless {
+    #[instrument(name = "wireless", skip_all, fields(device = %self.name))]
+    async fn tick(&self) {
+        let mut sent = Vec::new();
+        for (i, iface) in self.interfaces.iter().enumerate() {
+            if iface.is_connected() {
+                if let Some(symbol) = iface.recieve_symbol().await {
+                    sent.push((i, symbol));
+                }
+            }
+        }
+
+        for (to, iface) in self.interfaces.iter().enumerate() {
+            if !iface.is_connected() {
+                continue;
+            }
+            for (from, symbol) in sent.iter() {
+                if self.reachable(*from, to) {
+                    iface.transmit_symbol(*symbol).await;
+                }
+            }
+        }
+
+        for (from, iface) in self.interfaces.iter().enumerate() {
+            while let Some(frame) = iface.recieve_frame().await {
+                for (to, other) in self.interfaces.iter().enumerate() {
+                    if other.is_connected() && self.reachable(from, to) {
+                        other.relay_frame(frame.clone()).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct TestDevice {
+        nic: NIC,
+    }
+
+    impl PhysicalLayer for TestDevice {
+        fn nic(&self) -> &NIC {
+            &self.nic
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hidden_terminal() {
+        let positions = [
+            Position::new(0.0, 0.0),
+            Position::new(10.0, 0.0),
+            Position::new(20.0, 0.0),
+        ];
+        let medium = Arc::new(Wireless::new(15.0, positions).unwrap());
+        let devices: [Arc<TestDevice>; 3] = Default::default();
+        for device in &devices {
+            medium.connect(device.clone()).unwrap();
+        }
+
+        devices[0].transmit(0x09).await;
+        medium.tick().await;
+
+        assert_eq!(devices[1].receive().await, Some(0x09));
+        assert_eq!(devices[2].receive().await, None);
+        assert_eq!(devices[0].receive().await, None);
+    }
+}
@@ -0,0 +1,443 @@
+/*
+  802.11 Distributed Coordination Function (CSMA/CA)
+
+  Reference:
+    IEEE 802.11-2020, Clause 10.3 (DCF)
+*/
+use super::{
+    header::TypeLen,
+    media_access_control::{AccessControl, ReceivedFrame, TransmitStatus, DELIMITER_SIZE, MIN_FRAME_SIZE},
+    MacAddr,
+};
+use crate::layers::ReceiveError;
+use crate::utils::clock;
+use rand::Rng;
+use tracing::debug;
+
+/// IEEE 802 local experimental EtherType used to carry DCF control frames
+pub const DCF_CONTROL: TypeLen = 0x88B5;
+
+/// Duration of a backoff slot in byte times
+const SLOT_TIME: usize = 20;
+
+/// Short interframe space in byte times, separates the frames of one exchange
+const SIFS: usize = 10;
+
+/// DCF interframe space in byte times, the idle time required before contending
+const DIFS: usize = SIFS + 2 * SLOT_TIME;
+
+const CW_MIN: usize = 15;
+const CW_MAX: usize = 1023;
+
+/// Number of transmission attempts before a frame is dropped
+const RETRY_LIMIT: usize = 7;
+
+/// Time on the medium of a control frame (delimiter + minimum size frame) in byte times
+const CONTROL_TIME: usize = DELIMITER_SIZE + MIN_FRAME_SIZE;
+
+/// How long a station waits for a CTS or ACK before it assumes the exchange failed
+const RESPONSE_TIMEOUT: usize = SIFS + CONTROL_TIME + SLOT_TIME;
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFrame {
+    Rts = 1,
+    Cts = 2,
+    Ack = 3,
+}
+
+impl ControlFrame {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(ControlFrame::Rts),
+            2 => Some(ControlFrame::Cts),
+            3 => Some(ControlFrame::Ack),
+            _ => None,
+        }
+    }
+
+    /// Payload of a control frame: the kind followed by the duration field in byte times
+    pub fn to_payload(self, duration: u16) -> Vec<u8> {
+        let duration = duration.to_be_bytes();
+        vec![self as u8, duration[0], duration[1]]
+    }
+
+    pub fn from_payload(payload: &[u8]) -> Option<(Self, u16)> {
+        if payload.len() < 3 {
+            return None;
+        }
+        let kind = ControlFrame::from_u8(payload[0])?;
+        Some((kind, u16::from_be_bytes([payload[1], payload[2]])))
+    }
+}
+
+/// The medium is idle when no carrier is sensed and the network allocation vector has expired
+async fn medium_idle<T: AccessControl + ?Sized>(station: &T) -> bool {
+    !station.carrier_sense() && clock::now() >= station.receive_state().await.nav
+}
+
+/// Waits until the medium has been idle for `ifs` byte times
+async fn defer<T: AccessControl + ?Sized>(station: &T, ifs: usize) {
+    let mut idle = 0;
+    while idle < ifs {
+        tokio::time::sleep(clock::BYTE_TIME).await;
+        idle = if medium_idle(station).await { idle + 1 } else { 0 };
+    }
+}
+
+/// Counts down a random number of slots from the contention window.
+///
+/// The countdown is frozen while the medium is busy and resumes after another DIFS.
+async fn backoff<T: AccessControl + ?Sized>(station: &T, contention_window: usize) {
+    let mut slots = rand::thread_rng().gen_range(0..=contention_window);
+    debug!(contention_window, slots, "backoff");
+    while slots > 0 {
+        tokio::time::sleep(clock::byte_times(SLOT_TIME)).await;
+        if medium_idle(station).await {
+            slots -= 1;
+        } else {
+            defer(station, DIFS).await;
+        }
+    }
+}
+
+async fn send_control<T: AccessControl + ?Sized>(
+    station: &T,
+    kind: ControlFrame,
+    dest: &MacAddr,
+    duration: usize,
+) {
+    let payload = kind.to_payload(duration.min(u16::MAX as usize) as u16);
+    let frame = station.encapsulate_frame(dest, &station.mac(), DCF_CONTROL, payload);
+    station.send_frame(&frame).await;
+}
+
+/// Waits for a control frame of the given kind from `peer`, as recorded by `receive`
+async fn await_response<T: AccessControl + ?Sized>(
+    station: &T,
+    kind: ControlFrame,
+    peer: &MacAddr,
+) -> bool {
+    let deadline = clock::now() + clock::byte_times(RESPONSE_TIMEOUT);
+    while clock::now() < deadline {
+        let mut state = station.receive_state().await;
+        if let Some((received, from)) = state.response.take() {
+            if received == kind && &from == peer {
+                return true;
+            }
+        }
+        drop(state);
+        tokio::time::sleep(clock::BYTE_TIME).await;
+    }
+    false
+}
+
+/// Transmits a frame using the distributed coordination function.
+///
+/// Every attempt defers for DIFS and a random backoff from the contention window. With `rts_cts`
+/// the medium is reserved by an RTS/CTS exchange first, so hidden stations set their NAV. Unicast
+/// frames must be acknowledged, otherwise the contention window is doubled and the frame is retried.
+pub async fn transmit<T: AccessControl + ?Sized>(
+    station: &T,
+    dest: &MacAddr,
+    src: &MacAddr,
+    type_len: TypeLen,
+    frame: Vec<u8>,
+    rts_cts: bool,
+) -> Result<TransmitStatus, TransmitStatus> {
+    let data = station.encapsulate_frame(dest, src, type_len, frame);
+    let data_time = station.nic().framing().frame(&data).len();
+    let unicast = dest != &MacAddr::broadcast();
+    let mut contention_window = CW_MIN;
+
+    for _ in 0..RETRY_LIMIT {
+        defer(station, DIFS).await;
+        backoff(station, contention_window).await;
+
+        station.receive_state().await.response = None;
+        if rts_cts && unicast {
+            let duration = 3 * SIFS + CONTROL_TIME + data_time + CONTROL_TIME;
+            send_control(station, ControlFrame::Rts, dest, duration).await;
+            if !await_response(station, ControlFrame::Cts, dest).await {
+                debug!(%dest, "no CTS");
+                contention_window = (2 * contention_window + 1).min(CW_MAX);
+                continue;
+            }
+            tokio::time::sleep(clock::byte_times(SIFS)).await;
+        }
+
+        station.send_frame(&data).await;
+        if !unicast || await_response(station, ControlFrame::Ack, dest).await {
+            return Ok(TransmitStatus::Ok);
+        }
+        debug!(%dest, "no ACK");
+        contention_window = (2 * contention_window + 1).min(CW_MAX);
+    }
+
+    Err(TransmitStatus::RetryLimitExceeded)
+}
+
+/// Updates the NAV from a control frame overheard on the medium, whoever it is addressed to
+pub async fn observe<T: AccessControl + ?Sized>(station: &T, frame: &[u8]) {
+    // Destination, source and type/length precede the payload
+    if frame.len() < 17 || T::fcs(&frame.to_vec()) != 0 {
+        return;
+    }
+    if u16::from_be_bytes([frame[12], frame[13]]) != DCF_CONTROL {
+        return;
+    }
+    let mut dest = [0; 6];
+    dest.copy_from_slice(&frame[0..6]);
+    if MacAddr::from(dest) == station.mac() {
+        return;
+    }
+    if let Some((kind, duration)) = ControlFrame::from_payload(&frame[14..]) {
+        debug!(?kind, duration, "NAV set");
+        let mut state = station.receive_state().await;
+        state.nav = state.nav.max(clock::now() + clock::byte_times(duration as usize));
+    }
+}
+
+/// Handles the DCF side of a received frame.
+///
+/// Answers an RTS with a CTS and a unicast data frame with an ACK. CTS and ACK frames are recorded
+/// for a pending `transmit`. Returns `true` if the frame was consumed by the MAC and must not be
+/// passed to the client.
+pub async fn receive<T: AccessControl + ?Sized>(
+    station: &T,
+    status: &Result<ReceivedFrame, ReceiveError>,
+) -> bool {
+    let Ok(ReceivedFrame { dest, src, type_len, data }) = status else {
+        return false;
+    };
+
+    if *type_len != DCF_CONTROL {
+        if dest != &MacAddr::broadcast() {
+            tokio::time::sleep(clock::byte_times(SIFS)).await;
+            send_control(station, ControlFrame::Ack, src, 0).await;
+        }
+        return false;
+    }
+
+    // A station that has set its NAV for another exchange must not answer an RTS
+    let nav_expired = clock::now() >= station.receive_state().await.nav;
+    match ControlFrame::from_payload(data) {
+        Some((ControlFrame::Rts, duration)) if nav_expired => {
+            let remaining = (duration as usize).saturating_sub(SIFS + CONTROL_TIME);
+            tokio::time::sleep(clock::byte_times(SIFS)).await;
+            send_control(station, ControlFrame::Cts, src, remaining).await;
+        }
+        Some((ControlFrame::Rts, _)) => (),
+        Some((kind, _)) => station.receive_state().await.response = Some((kind, src.clone())),
+        None => (),
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::wireless::{Position, Wireless};
+    use crate::layers::datalink::{
+        media_access_control::{AccessMethod, ReceiveState, TransmitState},
+        ErrorControl,
+    };
+    use crate::layers::physical::Framing;
+    use crate::layers::{PhysicalLayer, NIC};
+    use crate::utils::Simulateable;
+    use std::sync::Arc;
+    use tokio::sync::{Mutex, MutexGuard};
+    use tokio::time::Duration;
+
+    #[derive(Default)]
+    struct TestStation {
+        nic: NIC,
+        transmit_state: Mutex<TransmitState>,
+        receive_state: Mutex<ReceiveState>,
+        /// Bytes sent and the simulation time they were sent at
+        sent: std::sync::Mutex<Vec<(Duration, u8)>>,
+        /// Frames passed to the MAC client
+        received: std::sync::Mutex<Vec<ReceivedFrame>>,
+    }
+
+    impl PhysicalLayer for TestStation {
+        fn nic(&self) -> &NIC {
+            &self.nic
+        }
+
+        async fn transmit(&self, byte: u8) {
+            self.sent.lock().unwrap().push((clock::now(), byte));
+            self.nic.transmit(byte).await;
+        }
+    }
+
+    impl ErrorControl for TestStation {}
+
+    impl AccessControl for TestStation {
+        async fn transmit_state(&self) -> MutexGuard<'_, TransmitState> {
+            self.transmit_state.lock().await
+        }
+
+        async fn receive_state(&self) -> MutexGuard<'_, ReceiveState> {
+            self.receive_state.lock().await
+        }
+    }
+
+    /// Attaches stations at the given positions to a running radio medium with a range of 15 m
+    fn medium(positions: &[f64]) -> Vec<Arc<TestStation>> {
+        let medium = Arc::new(Wireless::new(15.0, positions.iter().map(|&x| Position::new(x, 0.0))).unwrap());
+        let stations: Vec<Arc<TestStation>> = positions.iter().map(|_| Default::default()).collect();
+        for station in &stations {
+            station.nic().set_access_method(AccessMethod::CsmaCa { rts_cts: true });
+            medium.connect(station.clone()).unwrap();
+            let transmitter = station.clone();
+            tokio::spawn(async move { transmitter.byte_transmitter().await });
+        }
+        tokio::spawn(async move {
+            loop {
+                medium.tick().await;
+                tokio::time::sleep(clock::BYTE_TIME).await;
+            }
+        });
+        stations
+    }
+
+    /// Runs a MAC client that collects the frames the station receives
+    fn client(station: &Arc<TestStation>) {
+        let station = station.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Ok(status) = station.receive_frame().await {
+                    station.received.lock().unwrap().push(status);
+                }
+            }
+        });
+    }
+
+    async fn send(station: &TestStation, dest: &TestStation, octets: usize) -> Result<TransmitStatus, TransmitStatus> {
+        station.transmit_frame(&dest.mac(), &station.mac(), 0x0800, vec![0x42; octets]).await
+    }
+
+    /// The frames a station sent, told apart by the gaps between them, with the time each one ended
+    fn frames_sent(station: &TestStation) -> Vec<(Vec<u8>, Duration)> {
+        let mut bursts: Vec<(Vec<u8>, Duration)> = Vec::new();
+        for &(time, byte) in station.sent.lock().unwrap().iter() {
+            match bursts.last_mut() {
+                Some((burst, end)) if time <= *end + clock::byte_times(2) => {
+                    burst.push(byte);
+                    *end = time;
+                }
+                _ => bursts.push((vec![byte], time)),
+            }
+        }
+        bursts
+            .into_iter()
+            .filter_map(|(burst, end)| Some((Framing::Preamble.deframe(&burst).pop()?.ok()?, end)))
+            .collect()
+    }
+
+    /// The kind of a DCF control frame, `None` for a data frame
+    fn kind(frame: &[u8]) -> Option<ControlFrame> {
+        if u16::from_be_bytes([frame[12], frame[13]]) != DCF_CONTROL {
+            return None;
+        }
+        ControlFrame::from_payload(&frame[14..]).map(|(kind, _)| kind)
+    }
+
+    fn kinds(station: &TestStation) -> Vec<Option<ControlFrame>> {
+        frames_sent(station).iter().map(|(frame, _)| kind(frame)).collect()
+    }
+
+    fn received(station: &TestStation) -> usize {
+        station.received.lock().unwrap().len()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rts_cts_exchange() {
+        let stations = medium(&[0.0, 10.0]);
+        let (a, b) = (&stations[0], &stations[1]);
+        client(a);
+        client(b);
+
+        assert!(matches!(send(a, b, 100).await, Ok(TransmitStatus::Ok)));
+        assert_eq!(kinds(a), vec![Some(ControlFrame::Rts), None]);
+        assert_eq!(kinds(b), vec![Some(ControlFrame::Cts), Some(ControlFrame::Ack)]);
+        assert_eq!(received(b), 1);
+        assert_eq!(received(a), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_nav_defers_hidden_station() {
+        // A and C are out of range of each other, both reach B
+        let stations = medium(&[0.0, 10.0, 20.0]);
+        let (a, b, c) = (&stations[0], &stations[1], &stations[2]);
+        for station in &stations {
+            client(station);
+        }
+
+        let sender = a.clone();
+        let receiver = b.clone();
+        let exchange = tokio::spawn(async move { send(&sender, &receiver, 1000).await });
+        while clock::now() >= c.receive_state().await.nav {
+            tokio::time::sleep(clock::BYTE_TIME).await;
+        }
+
+        // C only hears B's CTS, its NAV keeps it from sending into A's data frame
+        assert!(send(c, b, 100).await.is_ok());
+        assert!(exchange.await.unwrap().is_ok());
+        let (_, ack_end) = frames_sent(b)[1];
+        let (first, _) = c.sent.lock().unwrap()[0];
+        assert!(first > ack_end, "{first:?} {ack_end:?}");
+        assert_eq!(received(b), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_without_ack() {
+        let stations = medium(&[0.0, 10.0]);
+        let (a, b) = (&stations[0], &stations[1]);
+        a.nic().set_access_method(AccessMethod::CsmaCa { rts_cts: false });
+        client(a);
+
+        let sender = a.clone();
+        let receiver = b.clone();
+        let exchange = tokio::spawn(async move { send(&sender, &receiver, 100).await });
+
+        // B only starts receiving once A has given up waiting for the first ACK
+        while frames_sent(a).is_empty() {
+            tokio::time::sleep(clock::BYTE_TIME).await;
+        }
+        tokio::time::sleep(clock::byte_times(RESPONSE_TIMEOUT + SIFS)).await;
+        while b.receive().await.is_some() {}
+        client(b);
+
+        assert!(exchange.await.unwrap().is_ok());
+        assert_eq!(kinds(a), vec![None, None]);
+        assert_eq!(kinds(b), vec![Some(ControlFrame::Ack)]);
+        assert_eq!(received(b), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_limit() {
+        // B never answers, so every attempt of A times out
+        let stations = medium(&[0.0, 10.0]);
+        let (a, b) = (&stations[0], &stations[1]);
+
+        assert!(matches!(send(a, b, 100).await, Err(TransmitStatus::RetryLimitExceeded)));
+        assert_eq!(kinds(a), vec![Some(ControlFrame::Rts); RETRY_LIMIT]);
+        let statistics = a.nic().statistics().snapshot();
+        assert_eq!(statistics.retry_limit_exceeded, 1);
+        assert_eq!(statistics.excessive_collisions, 0);
+        assert_eq!(statistics.collisions, 0);
+    }
+
+    #[test]
+    fn test_control_payload() {
+        let payload = ControlFrame::Rts.to_payload(1234);
+        assert_eq!(
+            ControlFrame::from_payload(&payload),
+            Some((ControlFrame::Rts, 1234))
+        );
+        assert_eq!(ControlFrame::from_payload(&[9, 0, 0]), None);
+        assert_eq!(ControlFrame::from_payload(&[1]), None);
+    }
+}
@@ -0,0 +1,594 @@
+/*
+  Reference:
+    ISO/IEC 13239 (High-level data link control procedures)
+*/
+use super::error_control::ErrorControl;
+use crate::layers::physical::PhysicalLayer;
+use crate::utils::clock;
+use futures::Future;
+use std::collections::VecDeque;
+use tokio::sync::MutexGuard;
+use tracing::{debug, trace, warn};
+
+/// Sequence numbers are counted modulo 8
+const MODULUS: u8 = 8;
+
+/// Maximum number of unacknowledged I-frames
+const WINDOW: usize = 7;
+
+const FCS_SIZE: usize = 4;
+
+/// Poll/final bit of the control field
+const POLL_FINAL: u8 = 0x10;
+
+/// Sends a frame with its FCS appended, a byte per byte time, delimited with the framing of the link
+pub(super) async fn send<T: PhysicalLayer + ErrorControl + ?Sized>(station: &T, mut frame: Vec<u8>) {
+    let octets = frame.len();
+    let fcs = T::fcs(&frame);
+    frame.extend(fcs.to_le_bytes());
+    for byte in station.nic().framing().frame(&frame) {
+        station.transmit(byte).await;
+        tokio::time::sleep(clock::BYTE_TIME).await;
+    }
+    station.nic().statistics().frames_out.increment();
+    station.nic().statistics().octets_out.add(octets as u64);
+}
+
+/// Reads the bytes received so far and returns the frames that pass the frame check, without FCS
+pub(super) async fn receive<T: PhysicalLayer + ErrorControl + ?Sized>(station: &T) -> Vec<Vec<u8>> {
+    let bytes = station.read_carrier().await;
+    let framing = station.nic().framing();
+
+    let statistics = station.nic().statistics();
+    let mut frames = Vec::new();
+    for result in framing.deframe(&bytes) {
+        match result {
+            Ok(mut frame) if frame.len() > FCS_SIZE && T::fcs(&frame) == 0 => {
+                frame.truncate(frame.len() - FCS_SIZE);
+                statistics.frames_in.increment();
+                statistics.octets_in.add(frame.len() as u64);
+                frames.push(frame);
+            }
+            Ok(frame) => {
+                debug!(octets = frame.len(), reason = "fcs mismatch", "frame rejected");
+                statistics.fcs_errors.increment();
+            }
+            Err(error) => {
+                debug!(?error, "framing error");
+                statistics.framing_errors.increment();
+            }
+        }
+    }
+    frames
+}
+
+/// Operational modes of a data link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Normal response mode, the secondary only transmits when polled by the primary
+    Nrm,
+    /// Asynchronous balanced mode, both combined stations transmit whenever they like
+    Abm,
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Supervisory {
+    ReceiveReady = 0,
+    ReceiveNotReady = 1,
+    Reject = 2,
+    SelectiveReject = 3,
+}
+
+pub(super) const SUPERVISORY: [Supervisory; 4] = [
+    Supervisory::ReceiveReady,
+    Supervisory::ReceiveNotReady,
+    Supervisory::Reject,
+    Supervisory::SelectiveReject,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unnumbered {
+    /// Set normal response mode
+    Snrm,
+    /// Set asynchronous balanced mode
+    Sabm,
+    Disc,
+    /// Unnumbered acknowledgement
+    Ua,
+    /// Disconnected mode
+    Dm,
+    /// Frame reject
+    Frmr,
+    /// Unnumbered information
+    Ui,
+}
+
+impl Unnumbered {
+    const CODES: [(Unnumbered, u8); 7] = [
+        (Unnumbered::Snrm, 0x83),
+        (Unnumbered::Sabm, 0x2F),
+        (Unnumbered::Disc, 0x43),
+        (Unnumbered::Ua, 0x63),
+        (Unnumbered::Dm, 0x0F),
+        (Unnumbered::Frmr, 0x87),
+        (Unnumbered::Ui, 0x03),
+    ];
+
+    fn code(&self) -> u8 {
+        Self::CODES.iter().find(|(kind, _)| kind == self).unwrap().1
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        Self::CODES.iter().find(|(_, c)| *c == code).map(|(kind, _)| *kind)
+    }
+}
+
+/// The control field of an HDLC frame, with modulo 8 sequence numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Information { ns: u8, nr: u8 },
+    Supervisory { kind: Supervisory, nr: u8 },
+    Unnumbered(Unnumbered),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HdlcFrame {
+    pub address: u8,
+    pub control: Control,
+    /// Poll bit of a command, final bit of a response
+    pub poll_final: bool,
+    pub information: Vec<u8>,
+}
+
+impl HdlcFrame {
+    /// Address, control and information fields, without FCS
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let pf = if self.poll_final { POLL_FINAL } else { 0 };
+        let control = match self.control {
+            Control::Information { ns, nr } => nr << 5 | pf | ns << 1,
+            Control::Supervisory { kind, nr } => nr << 5 | pf | (kind as u8) << 2 | 0b01,
+            Control::Unnumbered(kind) => kind.code() | pf,
+        };
+        [&[self.address, control], self.information.as_slice()].concat()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&address, rest) = bytes.split_first()?;
+        let (&control, information) = rest.split_first()?;
+        let nr = control >> 5;
+        let kind = match control & 0b11 {
+            0b01 => Control::Supervisory {
+                kind: SUPERVISORY[(control >> 2 & 0b11) as usize],
+                nr,
+            },
+            0b11 => Control::Unnumbered(Unnumbered::from_code(control & !POLL_FINAL)?),
+            _ => Control::Information {
+                ns: control >> 1 & 0b111,
+                nr,
+            },
+        };
+        Some(HdlcFrame {
+            address,
+            control: kind,
+            poll_final: control & POLL_FINAL != 0,
+            information: information.to_vec(),
+        })
+    }
+}
+
+/// State of an HDLC station.
+///
+/// There are no retransmission timers, lost I-frames are recovered with REJ.
+#[derive(Debug, Default)]
+pub struct HdlcState {
+    /// Address of the link, frames with other addresses are ignored
+    address: u8,
+    mode: Option<Mode>,
+    /// Whether this station set up the link, which makes it the primary in NRM
+    primary: bool,
+    /// Mode setting or disconnect command waiting for a UA
+    awaiting: Option<Unnumbered>,
+    /// Send state variable V(S)
+    vs: u8,
+    /// Receive state variable V(R)
+    vr: u8,
+    /// Oldest unacknowledged N(S)
+    va: u8,
+    unacknowledged: VecDeque<Vec<u8>>,
+    outgoing: VecDeque<Vec<u8>>,
+    received: VecDeque<Vec<u8>>,
+    /// A REJ has been sent and no in-sequence I-frame has arrived since
+    reject_sent: bool,
+    /// An in-sequence I-frame has not been acknowledged yet
+    ack_pending: bool,
+}
+
+impl HdlcState {
+    pub fn new(address: u8) -> Self {
+        HdlcState {
+            address,
+            ..Default::default()
+        }
+    }
+
+    /// The mode of the link, `None` while disconnected
+    pub fn mode(&self) -> Option<Mode> {
+        self.mode
+    }
+
+    fn reset(&mut self, mode: Option<Mode>, primary: bool) {
+        *self = HdlcState {
+            address: self.address,
+            mode,
+            primary,
+            outgoing: std::mem::take(&mut self.outgoing),
+            received: std::mem::take(&mut self.received),
+            ..Default::default()
+        };
+    }
+
+    /// Whether I-frames may be sent without being polled
+    fn may_send(&self) -> bool {
+        match self.mode {
+            Some(Mode::Abm) => true,
+            Some(Mode::Nrm) => self.primary,
+            None => false,
+        }
+    }
+
+    /// Removes the I-frames acknowledged by N(R)
+    fn acknowledge(&mut self, nr: u8) {
+        while self.va != nr && !self.unacknowledged.is_empty() {
+            self.unacknowledged.pop_front();
+            self.va = (self.va + 1) % MODULUS;
+        }
+    }
+
+    /// Takes queued data for I-frames as far as the window allows
+    fn next_information(&mut self) -> Vec<HdlcFrame> {
+        let mut frames = Vec::new();
+        while self.unacknowledged.len() < WINDOW {
+            let Some(data) = self.outgoing.pop_front() else {
+                break;
+            };
+            frames.push(self.information(self.vs, data.clone()));
+            self.unacknowledged.push_back(data);
+            self.vs = (self.vs + 1) % MODULUS;
+        }
+        self.ack_pending &= frames.is_empty();
+        frames
+    }
+
+    fn information(&self, ns: u8, information: Vec<u8>) -> HdlcFrame {
+        HdlcFrame {
+            address: self.address,
+            control: Control::Information { ns, nr: self.vr },
+            poll_final: false,
+            information,
+        }
+    }
+
+    fn supervisory(&self, kind: Supervisory, poll_final: bool) -> HdlcFrame {
+        HdlcFrame {
+            address: self.address,
+            control: Control::Supervisory { kind, nr: self.vr },
+            poll_final,
+            information: Vec::new(),
+        }
+    }
+
+    fn unnumbered(&self, kind: Unnumbered, poll_final: bool) -> HdlcFrame {
+        HdlcFrame {
+            address: self.address,
+            control: Control::Unnumbered(kind),
+            poll_final,
+            information: Vec::new(),
+        }
+    }
+
+    /// The response to a poll: queued I-frames with the final bit on the last, or RR
+    fn answer_poll(&mut self) -> Vec<HdlcFrame> {
+        let mut frames = self.next_information();
+        match frames.last_mut() {
+            Some(last) => last.poll_final = true,
+            None => frames.push(self.supervisory(Supervisory::ReceiveReady, true)),
+        }
+        self.ack_pending = false;
+        frames
+    }
+
+    /// Handles a received frame and returns the frames to send in response
+    fn handle(&mut self, frame: HdlcFrame) -> Vec<HdlcFrame> {
+        trace!(control = ?frame.control, poll_final = frame.poll_final, "frame received");
+        match frame.control {
+            Control::Unnumbered(Unnumbered::Snrm | Unnumbered::Sabm) => {
+                let mode = match frame.control {
+                    Control::Unnumbered(Unnumbered::Snrm) => Mode::Nrm,
+                    _ => Mode::Abm,
+                };
+                debug!(?mode, "link set up by peer");
+                self.reset(Some(mode), false);
+                vec![self.unnumbered(Unnumbered::Ua, frame.poll_final)]
+            }
+            Control::Unnumbered(Unnumbered::Disc) => {
+                debug!("link disconnected by peer");
+                self.reset(None, false);
+                vec![self.unnumbered(Unnumbered::Ua, frame.poll_final)]
+            }
+            Control::Unnumbered(Unnumbered::Ua) => {
+                match self.awaiting.take() {
+                    Some(Unnumbered::Snrm) => self.reset(Some(Mode::Nrm), true),
+                    Some(Unnumbered::Sabm) => self.reset(Some(Mode::Abm), true),
+                    Some(_) => self.reset(None, false),
+                    None => (),
+                }
+                debug!(mode = ?self.mode, "UA received");
+                Vec::new()
+            }
+            Control::Unnumbered(Unnumbered::Dm) => {
+                self.awaiting = None;
+                self.reset(None, false);
+                Vec::new()
+            }
+            Control::Unnumbered(Unnumbered::Frmr) => {
+                warn!(information = ?frame.information, "frame rejected by peer");
+                Vec::new()
+            }
+            Control::Unnumbered(Unnumbered::Ui) => {
+                self.received.push_back(frame.information);
+                Vec::new()
+            }
+            _ if self.mode.is_none() => vec![self.unnumbered(Unnumbered::Dm, frame.poll_final)],
+            Control::Information { ns, nr } => {
+                self.acknowledge(nr);
+                let mut responses = Vec::new();
+                if ns == self.vr {
+                    self.received.push_back(frame.information);
+                    self.vr = (self.vr + 1) % MODULUS;
+                    self.reject_sent = false;
+                    self.ack_pending = true;
+                } else if !self.reject_sent {
+                    debug!(ns, expected = self.vr, "out of sequence");
+                    self.reject_sent = true;
+                    responses.push(self.supervisory(Supervisory::Reject, false));
+                }
+                if frame.poll_final && !self.primary {
+                    responses.extend(self.answer_poll());
+                }
+                responses
+            }
+            Control::Supervisory { kind, nr } => {
+                self.acknowledge(nr);
+                let mut responses = Vec::new();
+                let resend: Vec<Vec<u8>> = match kind {
+                    Supervisory::Reject => self.unacknowledged.iter().cloned().collect(),
+                    Supervisory::SelectiveReject => self.unacknowledged.front().cloned().into_iter().collect(),
+                    _ => Vec::new(),
+                };
+                for (i, data) in resend.into_iter().enumerate() {
+                    debug!(ns = (self.va + i as u8) % MODULUS, "I-frame retransmitted");
+                    responses.push(self.information((self.va + i as u8) % MODULUS, data));
+                }
+                if frame.poll_final && !self.primary {
+                    responses.extend(self.answer_poll());
+                }
+                responses
+            }
+        }
+    }
+}
+
+/// High-level data link control over a point to point link.
+///
+/// The station is driven by `hdlc_service`, which handles the frames received since the
+/// last call and sends whatever the station may send.
+pub trait Hdlc: PhysicalLayer + ErrorControl {
+    fn hdlc_state(&self) -> impl Future<Output = MutexGuard<'_, HdlcState>>;
+
+    /// Asks the peer to set up the link in `mode`, in NRM this station becomes the primary
+    async fn hdlc_connect(&self, mode: Mode) {
+        let command = match mode {
+            Mode::Nrm => Unnumbered::Snrm,
+            Mode::Abm => Unnumbered::Sabm,
+        };
+        let frame = {
+            let mut state = self.hdlc_state().await;
+            state.awaiting = Some(command);
+            state.unnumbered(command, true)
+        };
+        send_frames(self, vec![frame]).await;
+    }
+
+    async fn hdlc_disconnect(&self) {
+        let frame = {
+            let mut state = self.hdlc_state().await;
+            state.awaiting = Some(Unnumbered::Disc);
+            state.unnumbered(Unnumbered::Disc, true)
+        };
+        send_frames(self, vec![frame]).await;
+    }
+
+    /// Queues data to be sent in an I-frame
+    async fn hdlc_send(&self, data: Vec<u8>) {
+        self.hdlc_state().await.outgoing.push_back(data);
+    }
+
+    /// Polls the secondary of an NRM link for its data
+    async fn hdlc_poll(&self) {
+        let frame = self.hdlc_state().await.supervisory(Supervisory::ReceiveReady, true);
+        send_frames(self, vec![frame]).await;
+    }
+
+    async fn hdlc_receive(&self) -> Option<Vec<u8>> {
+        self.hdlc_state().await.received.pop_front()
+    }
+
+    /// Handles received frames, then sends queued I-frames and outstanding acknowledgements
+    async fn hdlc_service(&self) {
+        for bytes in receive(self).await {
+            let Some(frame) = HdlcFrame::from_bytes(&bytes) else {
+                continue;
+            };
+            let responses = {
+                let mut state = self.hdlc_state().await;
+                if frame.address != state.address {
+                    continue;
+                }
+                state.handle(frame)
+            };
+            send_frames(self, responses).await;
+        }
+
+        let frames = {
+            let mut state = self.hdlc_state().await;
+            let mut frames = Vec::new();
+            if state.may_send() {
+                frames = state.next_information();
+                if let Some(last) = frames.last_mut() {
+                    last.poll_final = state.mode == Some(Mode::Nrm);
+                }
+            }
+            if state.ack_pending && state.may_send() {
+                state.ack_pending = false;
+                frames.push(state.supervisory(Supervisory::ReceiveReady, false));
+            }
+            frames
+        };
+        send_frames(self, frames).await;
+    }
+}
+
+async fn send_frames<T: Hdlc + ?Sized>(station: &T, frames: Vec<HdlcFrame>) {
+    for frame in frames {
+        trace!(control = ?frame.control, poll_final = frame.poll_final, "frame sent");
+        send(station, frame.to_bytes()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::physical::{Framing, LinkProperties};
+    use crate::layers::NIC;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    struct TestStation {
+        nic: NIC,
+        state: Mutex<HdlcState>,
+    }
+
+    impl Default for TestStation {
+        fn default() -> Self {
+            TestStation {
+                nic: NIC::default(),
+                state: Mutex::new(HdlcState::new(0x03)),
+            }
+        }
+    }
+
+    impl PhysicalLayer for TestStation {
+        fn nic(&self) -> &NIC {
+            &self.nic
+        }
+    }
+
+    impl ErrorControl for TestStation {}
+
+    impl Hdlc for TestStation {
+        async fn hdlc_state(&self) -> MutexGuard<'_, HdlcState> {
+            self.state.lock().await
+        }
+    }
+
+    #[test]
+    fn test_control_field() {
+        let sabm = HdlcFrame {
+            address: 0x03,
+            control: Control::Unnumbered(Unnumbered::Sabm),
+            poll_final: true,
+            information: Vec::new(),
+        };
+        assert_eq!(sabm.to_bytes(), [0x03, 0x3F]);
+
+        let information = HdlcFrame {
+            address: 0x03,
+            control: Control::Information { ns: 2, nr: 5 },
+            poll_final: true,
+            information: vec![0x42],
+        };
+        assert_eq!(information.to_bytes(), [0x03, 0xB4, 0x42]);
+        for frame in [sabm, information] {
+            assert_eq!(HdlcFrame::from_bytes(&frame.to_bytes()), Some(frame));
+        }
+    }
+
+    /// Properties of an HDLC link
+    fn properties() -> LinkProperties {
+        LinkProperties {
+            framing: Framing::BitStuffing,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_send_paced() {
+        let a = Arc::new(TestStation::default());
+        let b = Arc::new(TestStation::default());
+        a.connect_with(b.clone(), properties()).unwrap();
+
+        let start = tokio::time::Instant::now();
+        send(a.as_ref(), vec![0x03, 0x3F]).await;
+        let octets = a.nic().link_statistics().unwrap().octets_out as usize;
+        assert!(octets >= 2 + FCS_SIZE);
+        assert_eq!(start.elapsed(), clock::byte_times(octets));
+        assert_eq!(receive(b.as_ref()).await, vec![vec![0x03, 0x3F]]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_balanced_mode() {
+        let a = Arc::new(TestStation::default());
+        let b = Arc::new(TestStation::default());
+        a.connect_with(b.clone(), properties()).unwrap();
+
+        a.hdlc_connect(Mode::Abm).await;
+        b.hdlc_service().await;
+        a.hdlc_service().await;
+        assert_eq!(a.hdlc_state().await.mode(), Some(Mode::Abm));
+        assert_eq!(b.hdlc_state().await.mode(), Some(Mode::Abm));
+
+        for i in 0..3 {
+            a.hdlc_send(vec![i]).await;
+        }
+        a.hdlc_service().await;
+        b.hdlc_service().await;
+        a.hdlc_service().await;
+        for i in 0..3 {
+            assert_eq!(b.hdlc_receive().await, Some(vec![i]));
+        }
+        assert!(a.hdlc_state().await.unacknowledged.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_normal_response_mode() {
+        let primary = Arc::new(TestStation::default());
+        let secondary = Arc::new(TestStation::default());
+        primary.connect_with(secondary.clone(), properties()).unwrap();
+
+        primary.hdlc_connect(Mode::Nrm).await;
+        secondary.hdlc_service().await;
+        primary.hdlc_service().await;
+
+        // The secondary has to wait for a poll
+        secondary.hdlc_send(vec![0x42]).await;
+        secondary.hdlc_service().await;
+        primary.hdlc_service().await;
+        assert_eq!(primary.hdlc_receive().await, None);
+
+        primary.hdlc_poll().await;
+        secondary.hdlc_service().await;
+        primary.hdlc_service().await;
+        assert_eq!(primary.hdlc_receive().await, Some(vec![0x42]));
+    }
+}
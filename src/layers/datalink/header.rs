@@ -1,13 +1,18 @@
-use super::MacAddr;
+use super::{media_access_control::MIN_TYPE_VAL, MacAddr};
+use std::hash::{Hash, Hasher};
 
 pub type TypeLen = u16;
 
-#[repr(u16)]
+/// The protocol of a frame's payload, for type/length values of at least 1536.
+///
+/// EtherTypes compare by value, so `Unknown(0x0800)` is `IPv4`.
 #[derive(Copy, Clone, Debug)]
 pub enum EtherType {
-    IPv4 = 0x0800,
-    Arp = 0x0806,
-    IPv6 = 0x86DD,
+    IPv4,
+    Arp,
+    IPv6,
+    /// A protocol without a name here, still delivered to a handler registered for it
+    Unknown(u16),
 }
 
 impl From<u16> for EtherType {
@@ -16,7 +21,39 @@ impl From<u16> for EtherType {
             0x0800 => EtherType::IPv4,
             0x0806 => EtherType::Arp,
             0x86DD => EtherType::IPv6,
-            _ => panic!("Unknown EtherType: {:x}", value),
+            _ => EtherType::Unknown(value),
+        }
+    }
+}
+
+impl PartialEq for EtherType {
+    fn eq(&self, other: &Self) -> bool {
+        u16::from(*self) == u16::from(*other)
+    }
+}
+
+impl Eq for EtherType {}
+
+impl Hash for EtherType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        u16::from(*self).hash(state);
+    }
+}
+
+impl EtherType {
+    /// The EtherType of a type/length field, `None` if the field is a length
+    pub fn from_type_len(type_len: TypeLen) -> Option<Self> {
+        (type_len >= MIN_TYPE_VAL).then(|| EtherType::from(type_len))
+    }
+}
+
+impl From<EtherType> for u16 {
+    fn from(ether_type: EtherType) -> Self {
+        match ether_type {
+            EtherType::IPv4 => 0x0800,
+            EtherType::Arp => 0x0806,
+            EtherType::IPv6 => 0x86DD,
+            EtherType::Unknown(value) => value,
         }
     }
 }
@@ -1,7 +1,686 @@
-use super::media_access_control::AccessControl;
-use crate::layers::physical::PhysicalLayer;
+/*
+  Reference:
+    IEEE 802.2 (Logical Link Control)
+    IEEE 802, Clause 10 (Subnetwork Access Protocol)
+*/
+use super::hdlc::{Supervisory, SUPERVISORY};
+use super::media_access_control::{AccessControl, ReceivedFrame, TransmitStatus, MIN_TYPE_VAL};
+use super::protocols::{Delivery, Protocol, ProtocolRegistry};
+use super::{header::TypeLen, MacAddr};
+use futures::Future;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::{mpsc::UnboundedReceiver, MutexGuard};
+use tracing::{debug, trace, warn};
 
-pub trait LogicalLinkControl: PhysicalLayer + AccessControl {
-    async fn frame_transmitter();
-    async fn frame_reciever();
+/// The LLC sublayer itself, answers XID and TEST without a protocol bound to it
+pub const NULL_SAP: u8 = 0x00;
+/// Spanning tree protocol, BPDUs are sent as UI PDUs from and to this SAP
+pub const STP_SAP: u8 = 0x42;
+/// Subnetwork access protocol, the protocol is identified by the SNAP header that follows
+pub const SNAP_SAP: u8 = 0xAA;
+pub const GLOBAL_SAP: u8 = 0xFF;
+
+/// Command/response bit of the SSAP
+const RESPONSE: u8 = 0x01;
+
+/// Poll/final bit of an unnumbered control field
+const POLL_FINAL: u8 = 0x10;
+/// Poll/final bit in the second octet of a sequenced control field
+const POLL_FINAL_SEQUENCED: u8 = 0x01;
+
+/// Type 2 sequence numbers are counted modulo 128
+const MODULUS: u8 = 128;
+
+/// Maximum number of unacknowledged I-PDUs
+const WINDOW: usize = 7;
+
+const SNAP_HEADER_SIZE: usize = 5;
+
+/// XID information field: IEEE basic format, class II (Type 1 and 2), receive window
+const XID_INFORMATION: [u8; 3] = [0x81, 0x03, (WINDOW as u8) << 1];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unnumbered {
+    /// Unnumbered information, the Type 1 data transfer
+    Ui,
+    /// Exchange identification
+    Xid,
+    Test,
+    /// Set asynchronous balanced mode extended, sets up a Type 2 connection
+    Sabme,
+    Disc,
+    /// Unnumbered acknowledgement
+    Ua,
+    /// Disconnected mode
+    Dm,
+    /// Frame reject
+    Frmr,
+}
+
+impl Unnumbered {
+    const CODES: [(Unnumbered, u8); 8] = [
+        (Unnumbered::Ui, 0x03),
+        (Unnumbered::Xid, 0xAF),
+        (Unnumbered::Test, 0xE3),
+        (Unnumbered::Sabme, 0x6F),
+        (Unnumbered::Disc, 0x43),
+        (Unnumbered::Ua, 0x63),
+        (Unnumbered::Dm, 0x0F),
+        (Unnumbered::Frmr, 0x87),
+    ];
+
+    fn code(&self) -> u8 {
+        Self::CODES.iter().find(|(kind, _)| kind == self).unwrap().1
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        Self::CODES.iter().find(|(_, c)| *c == code).map(|(kind, _)| *kind)
+    }
+}
+
+/// The control field of an LLC PDU, with modulo 128 sequence numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Information { ns: u8, nr: u8 },
+    Supervisory { kind: Supervisory, nr: u8 },
+    Unnumbered(Unnumbered),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LlcPdu {
+    pub dsap: u8,
+    pub ssap: u8,
+    /// Whether the PDU is a response, the C/R bit of the SSAP
+    pub response: bool,
+    pub control: Control,
+    /// Poll bit of a command, final bit of a response
+    pub poll_final: bool,
+    pub information: Vec<u8>,
+}
+
+impl LlcPdu {
+    /// A UI PDU
+    pub fn ui(dsap: u8, ssap: u8, information: Vec<u8>) -> Self {
+        LlcPdu {
+            dsap,
+            ssap,
+            response: false,
+            control: Control::Unnumbered(Unnumbered::Ui),
+            poll_final: false,
+            information,
+        }
+    }
+
+    /// A UI PDU to the SNAP SAP, with a SNAP header for `protocol`
+    pub fn snap(oui: [u8; 3], protocol: u16, information: Vec<u8>) -> Self {
+        let information = [oui.as_ref(), &protocol.to_be_bytes(), &information].concat();
+        LlcPdu::ui(SNAP_SAP, SNAP_SAP, information)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let ssap = (self.ssap & !RESPONSE) | if self.response { RESPONSE } else { 0 };
+        let pf = self.poll_final as u8;
+        let control = match self.control {
+            Control::Information { ns, nr } => vec![ns << 1, nr << 1 | (pf * POLL_FINAL_SEQUENCED)],
+            Control::Supervisory { kind, nr } => vec![(kind as u8) << 2 | 0b01, nr << 1 | (pf * POLL_FINAL_SEQUENCED)],
+            Control::Unnumbered(kind) => vec![kind.code() | (pf * POLL_FINAL)],
+        };
+        [&[self.dsap, ssap], control.as_slice(), &self.information].concat()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (dsap, ssap, first) = (*bytes.first()?, *bytes.get(1)?, *bytes.get(2)?);
+        let (control, poll_final, information) = match first & 0b11 {
+            0b11 => {
+                let kind = Unnumbered::from_code(first & !POLL_FINAL)?;
+                (Control::Unnumbered(kind), first & POLL_FINAL != 0, &bytes[3..])
+            }
+            format => {
+                let second = *bytes.get(3)?;
+                let nr = second >> 1;
+                let control = match format {
+                    0b01 => Control::Supervisory {
+                        kind: SUPERVISORY[(first >> 2 & 0b11) as usize],
+                        nr,
+                    },
+                    _ => Control::Information { ns: first >> 1, nr },
+                };
+                (control, second & POLL_FINAL_SEQUENCED != 0, &bytes[4..])
+            }
+        };
+        Some(LlcPdu {
+            dsap,
+            ssap: ssap & !RESPONSE,
+            response: ssap & RESPONSE != 0,
+            control,
+            poll_final,
+            information: information.to_vec(),
+        })
+    }
+}
+
+/// Remote station, local SAP and remote SAP of a Type 2 connection
+type ConnectionId = (MacAddr, u8, u8);
+
+#[derive(Debug, Default)]
+struct Connection {
+    open: bool,
+    /// SABME or DISC waiting for a UA
+    awaiting: Option<Unnumbered>,
+    /// Send state variable V(S)
+    vs: u8,
+    /// Receive state variable V(R)
+    vr: u8,
+    /// Oldest unacknowledged N(S)
+    va: u8,
+    unacknowledged: VecDeque<Vec<u8>>,
+    outgoing: VecDeque<Vec<u8>>,
+    /// A REJ has been sent and no in-sequence I-PDU has arrived since
+    reject_sent: bool,
+    /// An in-sequence I-PDU has not been acknowledged yet
+    ack_pending: bool,
+}
+
+impl Connection {
+    /// Removes the I-PDUs acknowledged by N(R)
+    fn acknowledge(&mut self, nr: u8) {
+        while self.va != nr && !self.unacknowledged.is_empty() {
+            self.unacknowledged.pop_front();
+            self.va = (self.va + 1) % MODULUS;
+        }
+    }
+
+    /// Goes back to the oldest unacknowledged I-PDU, to send it and all after it again
+    fn rewind(&mut self) {
+        while let Some(data) = self.unacknowledged.pop_back() {
+            self.outgoing.push_front(data);
+        }
+        self.vs = self.va;
+    }
+}
+
+fn pdu(id: &ConnectionId, response: bool, control: Control, poll_final: bool, information: Vec<u8>) -> LlcPdu {
+    LlcPdu {
+        dsap: id.2,
+        ssap: id.1,
+        response,
+        control,
+        poll_final,
+        information,
+    }
+}
+
+/// Type 2 connections of the LLC sublayer of a station.
+///
+/// Protocols are bound in the station's `ProtocolRegistry`, which the received data is handed to.
+#[derive(Debug, Default)]
+pub struct LlcState {
+    connections: HashMap<ConnectionId, Connection>,
+}
+
+fn is_bound(protocols: &ProtocolRegistry, sap: u8) -> bool {
+    sap == NULL_SAP || protocols.is_registered(Protocol::Sap(sap))
+}
+
+impl LlcState {
+    /// Drops the connections of a SAP that is no longer bound
+    fn unbind(&mut self, sap: u8) {
+        self.connections.retain(|id, _| id.1 != sap);
+    }
+
+    pub fn is_connected(&self, remote: &MacAddr, local: u8, sap: u8) -> bool {
+        self.connections
+            .get(&(remote.clone(), local, sap))
+            .is_some_and(|connection| connection.open)
+    }
+
+    /// Starts setting up a connection and returns the SABME to send
+    fn connect(&mut self, remote: &MacAddr, local: u8, sap: u8) -> LlcPdu {
+        let id = (remote.clone(), local, sap);
+        let outgoing = self.connections.remove(&id).map(|c| c.outgoing).unwrap_or_default();
+        self.connections.insert(
+            id.clone(),
+            Connection {
+                awaiting: Some(Unnumbered::Sabme),
+                outgoing,
+                ..Default::default()
+            },
+        );
+        pdu(&id, false, Control::Unnumbered(Unnumbered::Sabme), true, Vec::new())
+    }
+
+    /// Starts releasing a connection and returns the DISC to send, if there is a connection
+    fn disconnect(&mut self, remote: &MacAddr, local: u8, sap: u8) -> Option<LlcPdu> {
+        let id = (remote.clone(), local, sap);
+        let connection = self.connections.get_mut(&id)?;
+        connection.open = false;
+        connection.awaiting = Some(Unnumbered::Disc);
+        Some(pdu(&id, false, Control::Unnumbered(Unnumbered::Disc), true, Vec::new()))
+    }
+
+    /// Queues data for a connection, returns `false` if there is none
+    fn queue(&mut self, remote: &MacAddr, local: u8, sap: u8, data: Vec<u8>) -> bool {
+        match self.connections.get_mut(&(remote.clone(), local, sap)) {
+            Some(connection) if connection.open => {
+                connection.outgoing.push_back(data);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Handles a PDU received from `source` for `destination` and returns the PDUs to send back,
+    /// `None` if its SAP is not bound
+    fn handle(
+        &mut self,
+        protocols: &mut ProtocolRegistry,
+        source: MacAddr,
+        destination: MacAddr,
+        pdu: LlcPdu,
+    ) -> Option<Vec<LlcPdu>> {
+        trace!(dsap = pdu.dsap, ssap = pdu.ssap, control = ?pdu.control, "PDU received");
+        let id = (source.clone(), pdu.dsap, pdu.ssap);
+        let respond = |kind: Unnumbered, information: Vec<u8>| {
+            vec![self::pdu(&id, true, Control::Unnumbered(kind), pdu.poll_final, information)]
+        };
+        match pdu.control {
+            Control::Unnumbered(Unnumbered::Ui) => {
+                let (protocol, payload) = match pdu.information.get(..SNAP_HEADER_SIZE) {
+                    Some(snap) if pdu.dsap == SNAP_SAP => {
+                        let protocol = Protocol::snap([snap[0], snap[1], snap[2]], u16::from_be_bytes([snap[3], snap[4]]));
+                        (protocol, pdu.information[SNAP_HEADER_SIZE..].to_vec())
+                    }
+                    _ => (Protocol::Sap(pdu.dsap), pdu.information),
+                };
+                let delivery = Delivery {
+                    destination,
+                    source,
+                    ssap: Some(pdu.ssap),
+                    payload,
+                };
+                protocols.dispatch(protocol, delivery).then(Vec::new)
+            }
+            _ if !is_bound(protocols, pdu.dsap) => None,
+            Control::Unnumbered(Unnumbered::Xid) if !pdu.response => Some(respond(Unnumbered::Xid, XID_INFORMATION.to_vec())),
+            Control::Unnumbered(Unnumbered::Test) if !pdu.response => {
+                Some(respond(Unnumbered::Test, pdu.information.clone()))
+            }
+            Control::Unnumbered(Unnumbered::Xid | Unnumbered::Test) => Some(Vec::new()),
+            _ => Some(self.handle_connection(protocols, id, destination, pdu)),
+        }
+    }
+
+    /// Handles a Type 2 PDU
+    fn handle_connection(
+        &mut self,
+        protocols: &mut ProtocolRegistry,
+        id: ConnectionId,
+        destination: MacAddr,
+        pdu: LlcPdu,
+    ) -> Vec<LlcPdu> {
+        let respond = |kind: Unnumbered| vec![self::pdu(&id, true, Control::Unnumbered(kind), pdu.poll_final, Vec::new())];
+        match pdu.control {
+            Control::Unnumbered(Unnumbered::Sabme) => {
+                debug!(remote = %id.0, sap = id.1, "connection set up by peer");
+                let outgoing = self.connections.remove(&id).map(|c| c.outgoing).unwrap_or_default();
+                let connection = Connection {
+                    open: true,
+                    outgoing,
+                    ..Default::default()
+                };
+                self.connections.insert(id.clone(), connection);
+                respond(Unnumbered::Ua)
+            }
+            Control::Unnumbered(Unnumbered::Disc) => {
+                debug!(remote = %id.0, sap = id.1, "connection released by peer");
+                match self.connections.remove(&id).is_some_and(|c| c.open) {
+                    true => respond(Unnumbered::Ua),
+                    false => respond(Unnumbered::Dm),
+                }
+            }
+            Control::Unnumbered(Unnumbered::Ua) => {
+                let awaiting = self.connections.get_mut(&id).and_then(|c| c.awaiting.take());
+                match awaiting {
+                    Some(Unnumbered::Sabme) => self.connections.get_mut(&id).unwrap().open = true,
+                    Some(_) => drop(self.connections.remove(&id)),
+                    None => (),
+                }
+                debug!(remote = %id.0, sap = id.1, open = self.is_connected(&id.0, id.1, id.2), "UA received");
+                Vec::new()
+            }
+            Control::Unnumbered(Unnumbered::Dm) => {
+                self.connections.remove(&id);
+                Vec::new()
+            }
+            Control::Unnumbered(Unnumbered::Frmr) => {
+                warn!(information = ?pdu.information, "PDU rejected by peer");
+                Vec::new()
+            }
+            Control::Unnumbered(_) => Vec::new(),
+            Control::Information { .. } | Control::Supervisory { .. } => {
+                let Some(connection) = self.connections.get_mut(&id).filter(|c| c.open) else {
+                    return match pdu.response {
+                        true => Vec::new(),
+                        false => respond(Unnumbered::Dm),
+                    };
+                };
+
+                let mut responses = Vec::new();
+                let mut delivered = None;
+                match pdu.control {
+                    Control::Information { ns, nr } => {
+                        connection.acknowledge(nr);
+                        if ns == connection.vr {
+                            connection.vr = (connection.vr + 1) % MODULUS;
+                            connection.reject_sent = false;
+                            connection.ack_pending = true;
+                            delivered = Some(pdu.information.clone());
+                        } else if !connection.reject_sent {
+                            debug!(ns, expected = connection.vr, "out of sequence");
+                            connection.reject_sent = true;
+                            let control = Control::Supervisory {
+                                kind: Supervisory::Reject,
+                                nr: connection.vr,
+                            };
+                            responses.push(self::pdu(&id, true, control, false, Vec::new()));
+                        }
+                    }
+                    Control::Supervisory { kind, nr } => {
+                        connection.acknowledge(nr);
+                        if matches!(kind, Supervisory::Reject | Supervisory::SelectiveReject) {
+                            debug!(nr, "I-PDUs retransmitted");
+                            connection.rewind();
+                        }
+                    }
+                    Control::Unnumbered(_) => unreachable!(),
+                }
+
+                if pdu.poll_final && !pdu.response {
+                    let control = Control::Supervisory {
+                        kind: Supervisory::ReceiveReady,
+                        nr: connection.vr,
+                    };
+                    connection.ack_pending = false;
+                    responses.push(self::pdu(&id, true, control, true, Vec::new()));
+                }
+                if let Some(payload) = delivered {
+                    let delivery = Delivery {
+                        destination,
+                        source: id.0.clone(),
+                        ssap: Some(id.2),
+                        payload,
+                    };
+                    protocols.dispatch(Protocol::Sap(id.1), delivery);
+                }
+                responses
+            }
+        }
+    }
+
+    /// I-PDUs and acknowledgements waiting to be sent on the open connections
+    fn pending(&mut self) -> Vec<(MacAddr, LlcPdu)> {
+        let mut pdus = Vec::new();
+        for (id, connection) in self.connections.iter_mut().filter(|(_, c)| c.open) {
+            let mut sent = false;
+            while connection.unacknowledged.len() < WINDOW {
+                let Some(data) = connection.outgoing.pop_front() else {
+                    break;
+                };
+                let control = Control::Information {
+                    ns: connection.vs,
+                    nr: connection.vr,
+                };
+                pdus.push((id.0.clone(), pdu(id, false, control, false, data.clone())));
+                connection.unacknowledged.push_back(data);
+                connection.vs = (connection.vs + 1) % MODULUS;
+                sent = true;
+            }
+            if connection.ack_pending && !sent {
+                let control = Control::Supervisory {
+                    kind: Supervisory::ReceiveReady,
+                    nr: connection.vr,
+                };
+                pdus.push((id.0.clone(), pdu(id, true, control, false, Vec::new())));
+            }
+            connection.ack_pending = false;
+        }
+        pdus
+    }
+}
+
+/// IEEE 802.2 logical link control on top of the MAC.
+///
+/// Protocols bind a SAP, or an OUI and protocol behind the SNAP SAP, and receive the PDUs sent to
+/// it through the station's `ProtocolRegistry`, so protocols such as STP carry their PDUs in their
+/// native format. A SNAP header of the EtherType OUI reaches the protocol of that EtherType. Type 1 service sends UI
+/// PDUs; Type 2 service sets up a connection with SABME and sends sequenced I-PDUs. There is no
+/// acknowledgement timer, lost I-PDUs are recovered with REJ.
+pub trait LogicalLinkControl: AccessControl {
+    fn llc_state(&self) -> impl Future<Output = MutexGuard<'_, LlcState>>;
+
+    /// Sends a UI PDU, the connectionless Type 1 service
+    async fn llc_send(&self, dest: &MacAddr, dsap: u8, ssap: u8, information: Vec<u8>) -> Result<TransmitStatus, TransmitStatus> {
+        send_pdu(self, dest, LlcPdu::ui(dsap, ssap, information)).await
+    }
+
+    /// Sends a UI PDU with a SNAP header for `protocol`
+    async fn snap_send(&self, dest: &MacAddr, oui: [u8; 3], protocol: u16, information: Vec<u8>) -> Result<TransmitStatus, TransmitStatus> {
+        send_pdu(self, dest, LlcPdu::snap(oui, protocol, information)).await
+    }
+
+    /// Sets up a Type 2 connection from the `local` SAP to `sap` of `dest`
+    async fn llc_connect(&self, dest: &MacAddr, local: u8, sap: u8) {
+        let pdu = self.llc_state().await.connect(dest, local, sap);
+        let _ = send_pdu(self, dest, pdu).await;
+    }
+
+    async fn llc_disconnect(&self, dest: &MacAddr, local: u8, sap: u8) {
+        let pdu = self.llc_state().await.disconnect(dest, local, sap);
+        if let Some(pdu) = pdu {
+            let _ = send_pdu(self, dest, pdu).await;
+        }
+    }
+
+    /// Queues data for a Type 2 connection, returns `false` if it is not open
+    async fn llc_queue(&self, dest: &MacAddr, local: u8, sap: u8, data: Vec<u8>) -> bool {
+        self.llc_state().await.queue(dest, local, sap, data)
+    }
+
+    /// Binds a protocol, PDUs for protocols that are not bound are discarded
+    async fn llc_bind(&self, protocol: Protocol) -> UnboundedReceiver<Delivery> {
+        self.register_protocol(protocol).await
+    }
+
+    async fn llc_unbind(&self, protocol: Protocol) {
+        self.unregister_protocol(protocol).await;
+        if let Protocol::Sap(sap) = protocol {
+            self.llc_state().await.unbind(sap);
+        }
+    }
+
+    /// Passes a frame received by the MAC to the protocol bound to its DSAP and answers it if needed
+    ///
+    /// Frames with a type instead of a length do not carry an LLC PDU and are ignored.
+    async fn llc_indicate(&self, frame: ReceivedFrame) {
+        let ReceivedFrame { dest, src, type_len, data } = frame;
+        if type_len >= MIN_TYPE_VAL {
+            return;
+        }
+        let Some(pdu) = LlcPdu::from_bytes(&data) else {
+            debug!(octets = data.len(), reason = "malformed LLC PDU", "frame rejected");
+            return;
+        };
+
+        let dsap = pdu.dsap;
+        let responses = {
+            let mut state = self.llc_state().await;
+            let mut receive_state = self.receive_state().await;
+            state.handle(&mut receive_state.protocols, src.clone(), dest, pdu)
+        };
+        match responses {
+            Some(responses) => {
+                for pdu in responses {
+                    let _ = send_pdu(self, &src, pdu).await;
+                }
+            }
+            None => {
+                debug!(dsap, reason = "unknown SAP", "frame rejected");
+                self.nic().statistics().unknown_saps.increment();
+            }
+        }
+    }
+
+    /// Receives and handles one frame, then sends what the connections have pending
+    async fn llc_service(&self) {
+        if let Ok(frame) = self.receive_frame().await {
+            self.llc_indicate(frame).await;
+        }
+        let pending = self.llc_state().await.pending();
+        for (dest, pdu) in pending {
+            let _ = send_pdu(self, &dest, pdu).await;
+        }
+    }
+}
+
+async fn send_pdu<T: LogicalLinkControl + ?Sized>(
+    station: &T,
+    dest: &MacAddr,
+    pdu: LlcPdu,
+) -> Result<TransmitStatus, TransmitStatus> {
+    trace!(dsap = pdu.dsap, ssap = pdu.ssap, control = ?pdu.control, "PDU sent");
+    let bytes = pdu.to_bytes();
+    station.transmit_frame(dest, &station.mac(), bytes.len() as TypeLen, bytes).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::datalink::{header::EtherType, protocols::ETHERTYPE_OUI};
+
+    /// SNA path control, an arbitrary SAP for the Type 2 tests
+    const SNA_SAP: u8 = 0x04;
+
+    /// The LLC of a station and the protocols bound on it
+    struct Llc {
+        mac: MacAddr,
+        state: LlcState,
+        protocols: ProtocolRegistry,
+    }
+
+    impl Llc {
+        fn new(station: u8) -> Self {
+            Llc {
+                mac: MacAddr::from([0x02, 0, 0, 0, 0, station]),
+                state: LlcState::default(),
+                protocols: ProtocolRegistry::default(),
+            }
+        }
+
+        fn handle(&mut self, source: &MacAddr, pdu: LlcPdu) -> Option<Vec<LlcPdu>> {
+            self.state.handle(&mut self.protocols, source.clone(), self.mac.clone(), pdu)
+        }
+    }
+
+    #[test]
+    fn test_pdu_format() {
+        let bpdu = vec![0x00, 0x00, 0x00, 0x80];
+        let ui = LlcPdu::ui(STP_SAP, STP_SAP, bpdu.clone());
+        assert_eq!(ui.to_bytes(), [&[0x42, 0x42, 0x03], bpdu.as_slice()].concat());
+
+        let snap = LlcPdu::snap(ETHERTYPE_OUI, 0x0800, vec![0x45]);
+        assert_eq!(snap.to_bytes(), [0xAA, 0xAA, 0x03, 0x00, 0x00, 0x00, 0x08, 0x00, 0x45]);
+
+        let information = LlcPdu {
+            dsap: SNA_SAP,
+            ssap: SNA_SAP,
+            response: true,
+            control: Control::Information { ns: 5, nr: 100 },
+            poll_final: true,
+            information: vec![0x01],
+        };
+        assert_eq!(information.to_bytes(), [0x04, 0x05, 0x0A, 0xC9, 0x01]);
+
+        for pdu in [ui, snap, information] {
+            assert_eq!(LlcPdu::from_bytes(&pdu.to_bytes()), Some(pdu));
+        }
+        assert_eq!(LlcPdu::from_bytes(&[0x04, 0x04, 0x00]), None);
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let station = MacAddr::from([0x02, 0, 0, 0, 0, 1]);
+        let mut llc = Llc::new(2);
+        let mut stp = llc.protocols.register(Protocol::Sap(STP_SAP));
+        // IPv4 behind a SNAP header reaches the same handler as IPv4 in the type field
+        let mut ipv4 = llc.protocols.register(EtherType::IPv4);
+
+        let bpdu = vec![0x00, 0x00, 0x00, 0x80];
+        assert_eq!(llc.handle(&station, LlcPdu::ui(STP_SAP, STP_SAP, bpdu.clone())), Some(vec![]));
+        assert_eq!(llc.handle(&station, LlcPdu::snap(ETHERTYPE_OUI, 0x0800, vec![0x45])), Some(vec![]));
+        assert_eq!(llc.handle(&station, LlcPdu::ui(0x06, 0x06, vec![0x45])), None);
+
+        let delivery = stp.try_recv().unwrap();
+        assert_eq!((delivery.source, delivery.ssap, delivery.payload), (station.clone(), Some(STP_SAP), bpdu));
+        assert_eq!(ipv4.try_recv().unwrap().payload, vec![0x45]);
+
+        let test = LlcPdu {
+            control: Control::Unnumbered(Unnumbered::Test),
+            poll_final: true,
+            ..LlcPdu::ui(NULL_SAP, STP_SAP, vec![1, 2, 3])
+        };
+        let responses = llc.handle(&station, test).unwrap();
+        assert_eq!(responses.len(), 1);
+        assert!(responses[0].response && responses[0].poll_final);
+        assert_eq!((responses[0].dsap, responses[0].ssap), (STP_SAP, NULL_SAP));
+        assert_eq!(responses[0].information, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_connection() {
+        let mut a = Llc::new(1);
+        let mut b = Llc::new(2);
+        let (a_mac, b_mac) = (a.mac.clone(), b.mac.clone());
+        let _a_sna = a.protocols.register(Protocol::Sap(SNA_SAP));
+        let mut b_sna = b.protocols.register(Protocol::Sap(SNA_SAP));
+
+        let sabme = a.state.connect(&b_mac, SNA_SAP, SNA_SAP);
+        let ua = b.handle(&a_mac, sabme).unwrap();
+        assert_eq!(a.handle(&b_mac, ua[0].clone()), Some(vec![]));
+        assert!(a.state.is_connected(&b_mac, SNA_SAP, SNA_SAP));
+        assert!(b.state.is_connected(&a_mac, SNA_SAP, SNA_SAP));
+
+        for data in [vec![1], vec![2], vec![3]] {
+            assert!(a.state.queue(&b_mac, SNA_SAP, SNA_SAP, data));
+        }
+        let mut sent = a.state.pending();
+        assert_eq!(sent.len(), 3);
+
+        // The second I-PDU is lost, the third one is rejected
+        sent.remove(1);
+        let mut responses = Vec::new();
+        for (_, pdu) in sent {
+            responses.extend(b.handle(&a_mac, pdu).unwrap());
+        }
+        assert!(matches!(responses[..], [LlcPdu { control: Control::Supervisory { kind: Supervisory::Reject, nr: 1 }, .. }]));
+
+        a.handle(&b_mac, responses.remove(0));
+        for (_, pdu) in a.state.pending() {
+            b.handle(&a_mac, pdu);
+        }
+        for (_, pdu) in b.state.pending() {
+            a.handle(&b_mac, pdu);
+        }
+        assert!(a.state.pending().is_empty());
+
+        let received: Vec<Vec<u8>> = std::iter::from_fn(|| b_sna.try_recv().ok())
+            .map(|delivery| delivery.payload)
+            .collect();
+        assert_eq!(received, vec![vec![1], vec![2], vec![3]]);
+
+        let disc = a.state.disconnect(&b_mac, SNA_SAP, SNA_SAP).unwrap();
+        let ua = b.handle(&a_mac, disc).unwrap();
+        a.handle(&b_mac, ua[0].clone());
+        assert!(!a.state.is_connected(&b_mac, SNA_SAP, SNA_SAP));
+        assert!(!b.state.is_connected(&a_mac, SNA_SAP, SNA_SAP));
+    }
 }
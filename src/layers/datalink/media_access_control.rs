@@ -1,51 +1,154 @@
 use super::{
+    collision_avoidance::{self, ControlFrame},
     error_control::ErrorControl,
-    header::{EthernetHeader, TypeLen},
+    header::{EtherType, EthernetHeader, TypeLen},
+    protocols::{Delivery, Protocol, ProtocolRegistry},
+    token_passing::{self, AccessControlField},
     MacAddr,
 };
 
-use crate::layers::physical::PhysicalLayer;
+use crate::layers::physical::{Duplex, Framing, LinkMode, PhysicalLayer, Symbol, PREAMBLE};
+use crate::layers::{PhysicalError, ReceiveError};
+use crate::utils::clock;
 use futures::{Future, FutureExt};
-use tokio::sync::MutexGuard;
-
-const FLAG: u8 = 0b10101011;
+use std::collections::VecDeque;
+use tokio::{
+    sync::{mpsc::UnboundedReceiver, MutexGuard},
+    time::Duration,
+};
+use tracing::{debug, instrument, trace, warn};
 
 /// Size of the slot in byte times
-const SLOT_SIZE: usize = 512;
+pub const SLOT_SIZE: usize = 512;
 
 /// Interframe space
 const IFS: usize = 12;
 
+/// Size of the jam sequence sent after a collision is detected
+const JAM_SIZE: usize = 4;
+const JAM: u8 = 0b10101010;
+
+/// Size of the preamble and start frame delimiter
+pub(super) const DELIMITER_SIZE: usize = PREAMBLE.len() + 1;
+
+/// Number of byte times after which no further frame may be started in a burst
+const BURST_LIMIT: usize = 8192;
+
 const CRC_SIZE: usize = 4;
 const ETHERNET_HEADER_SIZE: usize = 14;
 
 // Frame sizes
-const MIN_FRAME_SIZE: usize = 64;
+pub(super) const MIN_FRAME_SIZE: usize = 64;
 const MAX_BASIC_FRAME_SIZE: usize = 1518;
 const MAX_ENVELOPE_FRAME_SIZE: usize = 2000;
 
-const MIN_TYPE_VAL: u16 = 1536;
+pub(super) const MIN_TYPE_VAL: u16 = 1536;
 
 const EXTEND: bool = (SLOT_SIZE - MIN_FRAME_SIZE) > 0;
 
 const MAX_ATTEMPTS: usize = 16;
 const MAX_BACKOFF: usize = 10;
 
-const HALF_DUPLEX: bool = true;
+/// Size of an ALOHA slot in byte times, long enough to hold any basic frame
+const ALOHA_SLOT_SIZE: usize = MAX_BASIC_FRAME_SIZE + 1;
+
+/// The discipline a station uses to gain access to a shared medium
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AccessMethod {
+    /// Carrier sense multiple access with collision detection (IEEE 802.3)
+    #[default]
+    CsmaCd,
+    /// Transmit whenever a frame is ready, retransmit after a random delay on collision
+    PureAloha,
+    /// Like pure ALOHA, but transmissions may only start on a slot boundary
+    SlottedAloha,
+    /// Carrier sense multiple access with collision avoidance, the 802.11 DCF
+    ///
+    /// With `rts_cts` unicast frames are preceded by an RTS/CTS exchange that reserves the medium.
+    CsmaCa { rts_cts: bool },
+    /// Token passing around a physical ring (IEEE 802.5)
+    ///
+    /// Frames are sent with `priority` (0-7), exactly one station on the ring is the active `monitor`.
+    TokenRing { priority: u8, monitor: bool },
+    /// Token passing over a logical ring on a broadcast bus (IEEE 802.4)
+    ///
+    /// The token is handed to `successor` after use, one station is the `monitor` that recovers a lost token.
+    TokenBus { successor: MacAddr, monitor: bool },
+}
+
+impl AccessMethod {
+    /// Whether a transmission is aborted as soon as a collision is detected
+    pub fn aborts_on_collision(&self) -> bool {
+        matches!(self, AccessMethod::CsmaCd)
+    }
+
+    /// Whether the medium is shared by passing a token instead of contention
+    pub fn passes_token(&self) -> bool {
+        matches!(self, AccessMethod::TokenRing { .. } | AccessMethod::TokenBus { .. })
+    }
+
+    /// Theoretical throughput (successful frames per frame time) for an offered load `g`
+    ///
+    /// Peaks at 1/2e (~18%) for pure ALOHA and 1/e (~37%) for slotted ALOHA at `g = 0.5` and `g = 1`.
+    /// Other methods have no closed form here and return `None`.
+    pub fn throughput(&self, g: f64) -> Option<f64> {
+        match self {
+            AccessMethod::PureAloha => Some(g * (-2.0 * g).exp()),
+            AccessMethod::SlottedAloha => Some(g * (-g).exp()),
+            _ => None,
+        }
+    }
+
+    /// Waits a random number of slots (CSMA/CD) or frame times (ALOHA) before a retransmission
+    async fn backoff(&self, attempt: usize) {
+        use rand::Rng;
+        let max_backoff = 2usize.pow(attempt.min(MAX_BACKOFF) as u32);
+        let slots = rand::thread_rng().gen_range(0..max_backoff);
+        let slot_size = match self {
+            AccessMethod::PureAloha | AccessMethod::SlottedAloha => ALOHA_SLOT_SIZE,
+            _ => SLOT_SIZE,
+        };
+        debug!(attempt, slots, "backoff");
+        tokio::time::sleep(clock::byte_times(slots * slot_size)).await;
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum TransmitStatus {
     Ok,
     ExcessiveCollisions,
+    /// No CTS or ACK came back within the retry limit of a wireless medium
+    RetryLimitExceeded,
+    /// A collision was detected after the first slot time, e.g. because of a duplex mismatch
+    LateCollision,
+    /// The token did not come by in time, e.g. because the ring has no active monitor
+    NoToken,
+    /// The frame could not be handed to a frame mode link
+    Physical(PhysicalError),
 }
 
 pub struct TransmitState {
-    outgoing_frame: Vec<u8>,
+    outgoing_frame: Vec<Symbol>,
     attempts: usize,
     current_transmit_byte: usize,
     last_transmit_byte: usize,
     transmit_succeeding: bool,
     new_collision: bool,
+    late_collision: bool,
+    /// Simulation time at which the last frame was sent successfully, used for frame bursting
+    last_transmit_end: Option<Duration>,
+    /// Bytes sent since the start of the current burst
+    burst_bytes: usize,
+    /// Encapsulated frames waiting for the token, with the ticket handed out for each
+    pub(super) token_queue: VecDeque<(usize, Vec<u8>)>,
+    /// Ticket of the last frame queued
+    pub(super) token_ticket: usize,
+    /// Ticket of the last frame sent from `token_queue`
+    pub(super) token_sent: usize,
+    /// The token captured on a ring and the number of frames sent with it that have not returned yet
+    pub(super) circulating: Option<(AccessControlField, usize)>,
+    /// Token priority this station raised from and to, to be lowered again later
+    pub(super) stacked: Option<(u8, u8)>,
 }
 
 #[derive(Debug, Clone)]
@@ -54,6 +157,14 @@ pub struct ReceiveState {
     receiving: bool,
     receive_succeeeding: bool,
     valid_length: bool,
+    /// Network allocation vector, the simulation time until which the medium is reserved
+    pub(super) nav: Duration,
+    /// The last CSMA/CA control frame addressed to this station and its sender
+    pub(super) response: Option<(ControlFrame, MacAddr)>,
+    /// Frames received but not yet passed to the MAC client, from a token passing network or a burst
+    pub(super) copied: VecDeque<Vec<u8>>,
+    /// Protocols the payloads of received frames are handed to
+    pub(super) protocols: ProtocolRegistry,
 }
 
 impl Default for ReceiveState {
@@ -63,6 +174,10 @@ impl Default for ReceiveState {
             receiving: false,
             receive_succeeeding: false,
             valid_length: false,
+            nav: Duration::ZERO,
+            response: None,
+            copied: VecDeque::new(),
+            protocols: ProtocolRegistry::default(),
         }
     }
 }
@@ -76,15 +191,25 @@ impl Default for TransmitState {
             last_transmit_byte: 0,
             transmit_succeeding: false,
             new_collision: false,
+            late_collision: false,
+            last_transmit_end: None,
+            burst_bytes: 0,
+            token_queue: VecDeque::new(),
+            token_ticket: 0,
+            token_sent: 0,
+            circulating: None,
+            stacked: None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum ReceiveStatus {
-    Ok(MacAddr, MacAddr, u16, Vec<u8>),
-    FrameTooLong,
-    FrameCheckError,
+/// A frame passed to the MAC client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedFrame {
+    pub dest: MacAddr,
+    pub src: MacAddr,
+    pub type_len: TypeLen,
+    pub data: Vec<u8>,
 }
 
 pub trait AccessControl: PhysicalLayer + ErrorControl {
@@ -95,15 +220,47 @@ pub trait AccessControl: PhysicalLayer + ErrorControl {
         self.nic().mac()
     }
 
+    fn access_method(&self) -> AccessMethod {
+        self.nic().access_method()
+    }
+
+    /// Registers a protocol, the payloads of received frames of `protocol` are sent to the returned channel
+    async fn register_protocol(&self, protocol: impl Into<Protocol>) -> UnboundedReceiver<Delivery> {
+        self.receive_state().await.protocols.register(protocol)
+    }
+
+    async fn unregister_protocol(&self, protocol: impl Into<Protocol>) {
+        self.receive_state().await.protocols.unregister(protocol);
+    }
+
     /// An async process that watches for collisions on the network
     /// and sets the collision flag if a collision is detected
+    ///
+    /// ALOHA stations cannot listen while sending, so the frame is only marked as
+    /// failed and the transmission runs to completion.
+    ///
+    /// For CSMA/CD a collision after the first slot time is a late collision, which a
+    /// correctly sized half duplex network never produces.
     async fn watch_for_collision(&self) {
+        let method = self.access_method();
+        let aborts = method.aborts_on_collision();
+        let slotted = method == AccessMethod::CsmaCd && self.nic().duplex() == Duplex::Half;
         while self.transmitting() {
             let mut state = self.transmit_state().await;
             if state.transmit_succeeding && self.collision_detect() {
-                state.new_collision = true;
+                state.new_collision = aborts;
+                state.late_collision = slotted && state.current_transmit_byte > SLOT_SIZE;
                 state.transmit_succeeding = false;
+
+                debug!(byte = state.current_transmit_byte, late = state.late_collision, "collision");
+                let statistics = self.nic().statistics();
+                statistics.collisions.increment();
+                if state.late_collision {
+                    statistics.late_collisions.increment();
+                }
             }
+            drop(state);
+            tokio::time::sleep(clock::BYTE_TIME).await;
         }
     }
 
@@ -114,23 +271,46 @@ pub trait AccessControl: PhysicalLayer + ErrorControl {
         let pad_size = MIN_FRAME_SIZE.saturating_sub(ETHERNET_HEADER_SIZE + CRC_SIZE + frame.len());
         let header = EthernetHeader::new(src, dest, type_len);
         let mut encapsulated_frame = [
-            &[FLAG],
             header.to_be_bytes().as_ref(),
             frame.as_ref(),
-            vec![0b01010101; pad_size].as_ref(),
+            vec![0; pad_size].as_ref(),
         ].concat();
         let fcs = Self::fcs(&encapsulated_frame);
         encapsulated_frame.extend(fcs.to_le_bytes());
         encapsulated_frame
     }
 
+    /// Hands an already encapsulated frame to the byte transmitter as soon as it is idle,
+    /// and waits until the frame has been put on the medium
+    async fn send_frame(&self, frame: &[u8]) {
+        let frame = self.nic().framing().frame(frame);
+        let mut state = loop {
+            let state = self.transmit_state().await;
+            if !self.transmitting() {
+                break state;
+            }
+            drop(state);
+            tokio::time::sleep(clock::BYTE_TIME).await;
+        };
+        state.outgoing_frame = frame.into_iter().map(Symbol::Data).collect();
+        state.current_transmit_byte = 0;
+        state.last_transmit_byte = state.outgoing_frame.len();
+        state.new_collision = false;
+        self.nic().set_transmitting(true);
+        drop(state);
+
+        while self.transmitting() {
+            tokio::time::sleep(clock::BYTE_TIME).await;
+        }
+    }
+
     fn recognize_address(&self, destination: &MacAddr) -> bool {
         // TODO: Promiscuous and multicast mode
         destination == &MacAddr::broadcast() || destination == &self.mac()
     }
 
     /// Decapsulates a frame and returns the destination, source, type/length, and data
-    async fn decapsulate_frame(&self) -> Result<ReceiveStatus, ReceiveStatus> {
+    async fn decapsulate_frame(&self) -> Result<ReceivedFrame, ReceiveError> {
         fn remove_padding(type_len: TypeLen, data: Vec<u8>) -> Vec<u8> {
             if type_len >= MIN_TYPE_VAL {
                 return data;
@@ -147,59 +327,187 @@ pub trait AccessControl: PhysicalLayer + ErrorControl {
 
         // TODO: If we use Option, we can use .take() to get the frame and set it to None
         let mut frame = self.receive_state().await.incoming_frame.clone();
+        // Frames of a burst or copied from a ring have not been checked for their size yet
+        if frame.len() < MIN_FRAME_SIZE {
+            return Err(self.reject(ReceiveError::Runt(frame.len())));
+        }
         if Self::fcs(&frame) != 0 {
-            return Err(ReceiveStatus::FrameCheckError);
+            return Err(self.reject(ReceiveError::FcsMismatch));
         }
 
         let mut dest = [0; 6];
-        dest.copy_from_slice(&frame[1..7]);
+        dest.copy_from_slice(&frame[0..6]);
 
         self.receive_state().await.receive_succeeeding = self.recognize_address(&MacAddr::from(dest));
         if self.receive_state().await.receive_succeeeding {
             let mut src = [0; 6];
-            src.copy_from_slice(&frame[7..13]);
-            let type_len = u16::from_be_bytes([frame[13], frame[14]]);
-            frame.drain(..15);
+            src.copy_from_slice(&frame[6..12]);
+            let type_len = u16::from_be_bytes([frame[12], frame[13]]);
+            frame.drain(..ETHERNET_HEADER_SIZE);
             let data = remove_padding(type_len, frame);
             if data.len() > MAX_ENVELOPE_FRAME_SIZE {
-                return Err(ReceiveStatus::FrameTooLong);
+                return Err(self.reject(ReceiveError::TooLong(data.len())));
             }
-            return Ok(ReceiveStatus::Ok(
-                MacAddr::from(dest),
-                MacAddr::from(src),
+            debug!(
+                dest = %MacAddr::from(dest),
+                src = %MacAddr::from(src),
+                type_len,
+                octets = data.len(),
+                "frame accepted"
+            );
+            self.nic().statistics().frames_in.increment();
+            self.nic().statistics().octets_in.add(data.len() as u64);
+            if let Some(ether_type) = EtherType::from_type_len(type_len) {
+                let delivery = Delivery {
+                    destination: MacAddr::from(dest),
+                    source: MacAddr::from(src),
+                    ssap: None,
+                    payload: data.clone(),
+                };
+                if self.receive_state().await.protocols.dispatch(ether_type, delivery) {
+                    trace!(?ether_type, "payload dispatched");
+                }
+            }
+            return Ok(ReceivedFrame {
+                dest: MacAddr::from(dest),
+                src: MacAddr::from(src),
                 type_len,
                 data,
-            ));
+            });
+        }
+
+        Err(self.reject(ReceiveError::AddressFiltered(MacAddr::from(dest))))
+    }
+
+    /// Logs and counts a received frame that is not passed to the MAC client
+    fn reject(&self, error: ReceiveError) -> ReceiveError {
+        let statistics = self.nic().statistics();
+        match &error {
+            ReceiveError::AddressFiltered(dest) => {
+                trace!(%dest, reason = "address filtered", "frame rejected");
+                return error;
+            }
+            ReceiveError::Runt(_) => statistics.runts.increment(),
+            ReceiveError::TooLong(_) => statistics.frames_too_long.increment(),
+            ReceiveError::FcsMismatch => statistics.fcs_errors.increment(),
+            ReceiveError::AlignmentError => statistics.alignment_errors.increment(),
+            ReceiveError::Framing(_) => statistics.framing_errors.increment(),
+        }
+        debug!(%error, "frame rejected");
+        error
+    }
+
+    /// Delimits the frames in the bytes received during one carrier, counting framing errors
+    fn deframe(&self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        for result in self.nic().framing().deframe(bytes) {
+            match result {
+                Ok(frame) => frames.push(frame),
+                Err(error) => {
+                    self.reject(error.into());
+                }
+            }
         }
+        frames
+    }
 
-        Err(ReceiveStatus::FrameCheckError)
+    /// Waits for the next frame addressed to this station on a frame or signal mode link
+    async fn receive_whole_frame(&self) -> Result<ReceivedFrame, ReceiveError> {
+        loop {
+            let Some(frame) = self.nic().recieve_frame().await else {
+                tokio::time::sleep(clock::BYTE_TIME).await;
+                continue;
+            };
+            self.receive_state()
+                .map(|mut state| {
+                    state.incoming_frame = frame;
+                    state.receive_succeeeding = true;
+                })
+                .await;
+            let result = self.decapsulate_frame().await;
+            if self.receive_state().await.receive_succeeeding {
+                return result;
+            }
+        }
     }
 
-    /// An async process that is continuously running and transmits bytes on the network
+    /// An async process that is continuously running and transmits bytes on the network,
+    /// one byte per byte time
+    ///
+    /// A detected collision is enforced with a jam sequence so every station on the
+    /// segment sees it, then the transmission is aborted.
+    #[instrument(name = "nic", skip_all, fields(mac = %self.mac()))]
     async fn byte_transmitter(&self) {
         loop {
-            if self.transmitting() {
-                loop {
-                    while self.transmitting() {
-                        let mut state = self.transmit_state().await;
-                        self.transmit(state.outgoing_frame[state.current_transmit_byte]).await;
-                        if state.new_collision {
-                            state.current_transmit_byte = 1;
-                            state.new_collision = false;
-                            self.nic().set_transmitting(false);
-                        } else {
-                            state.current_transmit_byte += 1;
-                            self.nic().set_transmitting(state.current_transmit_byte < state.last_transmit_byte);
-                        }
+            while self.transmitting() {
+                let mut state = self.transmit_state().await;
+                let symbol = state.outgoing_frame[state.current_transmit_byte];
+                trace!(index = state.current_transmit_byte, ?symbol, "byte transmitted");
+                match symbol {
+                    Symbol::Data(byte) => self.transmit(byte).await,
+                    Symbol::Extension => self.extend().await,
+                }
+                if state.new_collision {
+                    debug!("jam");
+                    state.current_transmit_byte = 1;
+                    state.new_collision = false;
+                    drop(state);
+                    for _ in 0..JAM_SIZE {
+                        tokio::time::sleep(clock::BYTE_TIME).await;
+                        self.transmit(JAM).await;
                     }
+                    self.nic().set_transmitting(false);
+                } else {
+                    state.current_transmit_byte += 1;
+                    self.nic().set_transmitting(state.current_transmit_byte < state.last_transmit_byte);
+                    drop(state);
                 }
+                tokio::time::sleep(clock::BYTE_TIME).await;
             }
+            tokio::time::sleep(clock::BYTE_TIME).await;
         }
     }
 
+    /// Waits out the interframe gap before a transmission.
+    ///
+    /// On a half duplex link the station first defers to a passing carrier, and starts over
+    /// if a carrier appears during the gap.
+    async fn deference(&self) {
+        let half_duplex = self.nic().duplex() == Duplex::Half;
+        loop {
+            while half_duplex && self.carrier_sense() {
+                tokio::time::sleep(clock::BYTE_TIME).await;
+            }
+            tokio::time::sleep(clock::byte_times(IFS)).await;
+            if !(half_duplex && self.carrier_sense()) {
+                break;
+            }
+        }
+    }
+
+    /// An async process that is continuously running on token passing networks
+    ///
+    /// Repeats and copies frames and transmits queued frames whenever the station holds the token.
+    #[instrument(name = "nic", skip_all, fields(mac = %self.mac()))]
+    async fn token_passer(&self) {
+        token_passing::run(self).await;
+    }
+
     /// The interface for MAC Client by which it can transmit a frame
     ///
-    /// Uses the CSMA/CD algorithm to transmit the frame
+    /// Uses the NIC's `AccessMethod` (CSMA/CD by default) to transmit the frame.
+    /// On a full duplex link collisions cannot occur, so the frame is sent on the first attempt.
+    /// Frames that suffer a late collision are not retransmitted.
+    ///
+    /// On a frame or signal mode link the frame is handed to the link as a whole, the link
+    /// serialises frames so no access method is needed.
+    ///
+    /// Frames are delimited with the framing of the link. With the default preamble framing,
+    /// frames shorter than the slot on a half duplex link are followed by carrier extension.
+    /// A frame handed over within the interframe gap of a successful one continues the burst:
+    /// the gap is filled with extension and the frame is sent without deferring, until
+    /// `BURST_LIMIT` byte times have been used.
+    #[instrument(name = "nic", skip_all, fields(mac = %self.mac()))]
     async fn transmit_frame(
         &self,
         dest: &MacAddr,
@@ -207,22 +515,66 @@ pub trait AccessControl: PhysicalLayer + ErrorControl {
         type_len: TypeLen,
         frame: Vec<u8>,
     ) -> Result<TransmitStatus, TransmitStatus> {
-        async fn backoff(attempt: usize) {
-            use rand::Rng;
-            use std::time::Duration;
-            let max_backoff = 2usize.pow(attempt.min(MAX_BACKOFF) as u32);
-            let backoff = (rand::thread_rng().gen_range(0..max_backoff) * SLOT_SIZE) as u64;
-            tokio::time::sleep(Duration::from_millis(backoff)).await;
+        let method = self.access_method();
+        let octets = frame.len();
+        if self.nic().mode() != LinkMode::Byte {
+            let frame = self.encapsulate_frame(dest, src, type_len, frame);
+            let result = match self.nic().transmit_frame(frame).await {
+                Ok(_) => Ok(TransmitStatus::Ok),
+                Err(error) => Err(TransmitStatus::Physical(error)),
+            };
+            self.count_transmit(&result, octets);
+            return result;
+        }
+        if let AccessMethod::CsmaCa { rts_cts } = method {
+            let result = collision_avoidance::transmit(self, dest, src, type_len, frame, rts_cts).await;
+            self.count_transmit(&result, octets);
+            return result;
+        }
+        if method.passes_token() {
+            let frame = self.encapsulate_frame(dest, src, type_len, frame);
+            let result = token_passing::transmit(self, frame).await;
+            self.count_transmit(&result, octets);
+            return result;
         }
 
+        let framing = self.nic().framing();
+        let extend = EXTEND
+            && method == AccessMethod::CsmaCd
+            && self.nic().duplex() == Duplex::Half
+            && framing == Framing::Preamble;
         let mut state = self.transmit_state().await;
-        state.outgoing_frame = self.encapsulate_frame(dest, src, type_len, frame);
+        let framed = framing.frame(&self.encapsulate_frame(dest, src, type_len, frame));
+        let mut outgoing: Vec<Symbol> = framed.into_iter().map(Symbol::Data).collect();
+
+        let bursting = extend
+            && state.burst_bytes < BURST_LIMIT
+            && state
+                .last_transmit_end
+                .is_some_and(|end| clock::now() <= end + clock::byte_times(IFS));
+        if bursting {
+            outgoing = [vec![Symbol::Extension; IFS], outgoing].concat();
+        } else {
+            state.burst_bytes = 0;
+            if extend && outgoing.len() < DELIMITER_SIZE + SLOT_SIZE {
+                outgoing.resize(DELIMITER_SIZE + SLOT_SIZE, Symbol::Extension);
+            }
+        }
+
+        state.outgoing_frame = outgoing;
         state.attempts = 0;
         state.transmit_succeeding = false;
+        state.late_collision = false;
 
-        while state.attempts < MAX_ATTEMPTS && !state.transmit_succeeding {
+        while state.attempts < MAX_ATTEMPTS && !state.transmit_succeeding && !state.late_collision {
             if state.attempts > 0 {
-                backoff(state.attempts).await;
+                method.backoff(state.attempts).await;
+            }
+
+            if method == AccessMethod::SlottedAloha {
+                clock::next_slot(clock::byte_times(ALOHA_SLOT_SIZE)).await;
+            } else if method == AccessMethod::CsmaCd && !(bursting && state.attempts == 0) {
+                self.deference().await;
             }
 
             state.current_transmit_byte = 0;
@@ -238,14 +590,62 @@ pub trait AccessControl: PhysicalLayer + ErrorControl {
         }
 
         if state.transmit_succeeding {
+            state.last_transmit_end = Some(clock::now());
+            state.burst_bytes += state.outgoing_frame.len();
+            self.count_transmit(&Ok(TransmitStatus::Ok), octets);
             return Ok(TransmitStatus::Ok);
         }
+        state.last_transmit_end = None;
+        if state.late_collision {
+            self.count_transmit(&Err(TransmitStatus::LateCollision), octets);
+            return Err(TransmitStatus::LateCollision);
+        }
 
+        self.count_transmit(&Err(TransmitStatus::ExcessiveCollisions), octets);
         Err(TransmitStatus::ExcessiveCollisions)
     }
 
-    async fn receive_frame(&self) -> Result<ReceiveStatus, ReceiveStatus> {
-        let mut result = Err(ReceiveStatus::FrameCheckError);
+    /// Updates the NIC's counters with the outcome of `transmit_frame`
+    fn count_transmit(&self, result: &Result<TransmitStatus, TransmitStatus>, octets: usize) {
+        let statistics = self.nic().statistics();
+        match result {
+            Ok(_) => {
+                debug!(octets, "frame transmitted");
+                statistics.frames_out.increment();
+                statistics.octets_out.add(octets as u64);
+            }
+            Err(TransmitStatus::ExcessiveCollisions) => {
+                warn!(octets, "frame dropped after excessive collisions");
+                statistics.excessive_collisions.increment();
+            }
+            Err(TransmitStatus::RetryLimitExceeded) => {
+                warn!(octets, "frame dropped at the retry limit");
+                statistics.retry_limit_exceeded.increment();
+            }
+            Err(status) => warn!(octets, ?status, "frame dropped"),
+        }
+    }
+
+    #[instrument(name = "nic", skip_all, fields(mac = %self.mac()))]
+    async fn receive_frame(&self) -> Result<ReceivedFrame, ReceiveError> {
+        if self.nic().mode() != LinkMode::Byte {
+            return self.receive_whole_frame().await;
+        }
+        if self.access_method().passes_token() {
+            let frame = token_passing::receive(self).await;
+            self.receive_state().await.incoming_frame = frame;
+            return self.decapsulate_frame().await;
+        }
+
+        let burst = self.receive_state().await.copied.pop_front();
+        if let Some(frame) = burst {
+            self.receive_state().await.incoming_frame = frame;
+            return self.decapsulate_frame().await;
+        }
+
+        // Left set by the previous frame
+        self.receive_state().await.receive_succeeeding = false;
+        let mut result = Err(ReceiveError::FcsMismatch);
         while !self.receive_state().await.receive_succeeeding {
             while !self.receive_state().await.receive_succeeeding {
                 self.receive_state()
@@ -256,16 +656,21 @@ pub trait AccessControl: PhysicalLayer + ErrorControl {
                     .await;
 
                 if self.receive_state().await.receiving {
-                    let mut frame = Vec::new();
-                    while self.carrier_sense() {
-                        if let Some(byte) = self.receive().await {
-                            frame.push(byte);
-                        }
+                    if self.carrier_sense() {
+                        trace!("carrier up");
+                    }
+                    let frame = self.read_carrier().await;
+                    if frame.is_empty() {
+                        tokio::time::sleep(clock::BYTE_TIME).await;
+                    } else {
+                        trace!(octets = frame.len(), "carrier down");
                     }
 
+                    let mut frames = self.deframe(&frame).into_iter();
                     self.receive_state()
                         .map(|mut state| {
-                            state.incoming_frame = frame;
+                            state.incoming_frame = frames.next().unwrap_or_default();
+                            state.copied.extend(frames);
                             state.receiving = false;
                             state.receive_succeeeding = true;
                         })
@@ -277,11 +682,372 @@ pub trait AccessControl: PhysicalLayer + ErrorControl {
                         let frame_size = state.incoming_frame.len();
                         state.receive_succeeeding =
                             state.receive_succeeeding && frame_size >= MIN_FRAME_SIZE;
+                        if frame_size > 0 && frame_size < MIN_FRAME_SIZE {
+                            self.reject(ReceiveError::Runt(frame_size));
+                        }
                     })
                     .await;
             }
+            let avoids_collisions = matches!(self.access_method(), AccessMethod::CsmaCa { .. });
+            if avoids_collisions {
+                let frame = self.receive_state().await.incoming_frame.clone();
+                collision_avoidance::observe(self, &frame).await;
+            }
+
             result = self.decapsulate_frame().await;
+            if avoids_collisions && collision_avoidance::receive(self, &result).await {
+                self.receive_state().await.receive_succeeeding = false;
+            }
         }
         return result;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::{DuplexSetting, LinkProperties, NIC};
+    use std::ops::Range;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    /// Logged in place of a carrier extension symbol
+    const EXTENSION: u8 = 0x0F;
+
+    /// A station that logs the bytes it puts on the medium
+    #[derive(Default)]
+    struct TestStation {
+        nic: NIC,
+        transmit_state: Mutex<TransmitState>,
+        receive_state: Mutex<ReceiveState>,
+        /// Bytes sent and the simulation time they were sent at
+        sent: std::sync::Mutex<Vec<(Duration, u8)>>,
+        /// Another station is sensed while the number of bytes sent is in this range
+        interference: std::sync::Mutex<Range<usize>>,
+    }
+
+    impl TestStation {
+        fn new(method: AccessMethod, duplex: DuplexSetting) -> Arc<Self> {
+            let station = TestStation {
+                nic: NIC::with_duplex(duplex),
+                ..Default::default()
+            };
+            station.nic.set_access_method(method);
+            Arc::new(station)
+        }
+
+        fn sent(&self) -> Vec<u8> {
+            self.sent.lock().unwrap().iter().map(|(_, byte)| *byte).collect()
+        }
+
+        /// Transmits a frame of `octets` bytes of data while the byte transmitter runs
+        async fn send(&self, octets: usize) -> Result<TransmitStatus, TransmitStatus> {
+            let (dest, src) = (MacAddr::broadcast(), self.mac());
+            tokio::select! {
+                result = self.transmit_frame(&dest, &src, 0x0800, vec![0x42; octets]) => result,
+                _ = self.byte_transmitter() => unreachable!(),
+            }
+        }
+    }
+
+    impl PhysicalLayer for TestStation {
+        fn nic(&self) -> &NIC {
+            &self.nic
+        }
+
+        async fn transmit(&self, byte: u8) {
+            self.sent.lock().unwrap().push((clock::now(), byte));
+            self.nic.transmit(byte).await;
+        }
+
+        async fn extend(&self) {
+            self.sent.lock().unwrap().push((clock::now(), EXTENSION));
+            self.nic.transmit_symbol(Symbol::Extension).await;
+        }
+
+        fn carrier_sense(&self) -> bool {
+            let sent = self.sent.lock().unwrap().len();
+            self.nic.is_receiving() || self.interference.lock().unwrap().contains(&sent)
+        }
+    }
+
+    impl ErrorControl for TestStation {}
+
+    impl AccessControl for TestStation {
+        async fn transmit_state(&self) -> MutexGuard<'_, TransmitState> {
+            self.transmit_state.lock().await
+        }
+
+        async fn receive_state(&self) -> MutexGuard<'_, ReceiveState> {
+            self.receive_state.lock().await
+        }
+    }
+
+    /// Length of a frame with `octets` bytes of data on the medium
+    fn framed(octets: usize) -> usize {
+        DELIMITER_SIZE + ETHERNET_HEADER_SIZE + octets.max(MIN_FRAME_SIZE - ETHERNET_HEADER_SIZE - CRC_SIZE) + CRC_SIZE
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_aloha_retransmits_after_collision() {
+        for method in [AccessMethod::PureAloha, AccessMethod::SlottedAloha] {
+            let station = TestStation::new(method.clone(), DuplexSetting::Forced(Duplex::Half));
+            let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+            station.connect(partner.clone()).unwrap();
+            *station.interference.lock().unwrap() = 20..24;
+
+            assert!(matches!(station.send(100).await, Ok(TransmitStatus::Ok)), "{method:?}");
+            // The collided frame is sent to the end without a jam, then the whole frame again
+            let frame = framed(100);
+            let sent = station.sent();
+            assert_eq!(sent.len(), 2 * frame, "{method:?}");
+            assert_eq!(sent[..frame], sent[frame..]);
+            assert_eq!(station.nic().statistics().collisions.get(), 1);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_aloha_late_collision() {
+        let station = TestStation::new(AccessMethod::PureAloha, DuplexSetting::Forced(Duplex::Half));
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        station.connect(partner.clone()).unwrap();
+        *station.interference.lock().unwrap() = SLOT_SIZE + 20..SLOT_SIZE + 24;
+
+        // ALOHA has no slot time, so a collision is retransmitted wherever it happens
+        assert!(matches!(station.send(1000).await, Ok(TransmitStatus::Ok)));
+        assert_eq!(station.sent().len(), 2 * framed(1000));
+        assert_eq!(station.nic().statistics().collisions.get(), 1);
+        assert_eq!(station.nic().statistics().late_collisions.get(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_full_duplex() {
+        let station = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Auto);
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Auto);
+        station.connect(partner.clone()).unwrap();
+        assert_eq!(station.nic().duplex(), Duplex::Full);
+
+        // The partner's bytes are never read, so a half duplex station would defer forever
+        for _ in 0..10 {
+            partner.nic().transmit(0x42).await;
+        }
+        assert!(station.carrier_sense());
+        let result = tokio::time::timeout(clock::byte_times(1000), station.send(10)).await;
+        assert!(matches!(result, Ok(Ok(TransmitStatus::Ok))));
+
+        // Neither extended to the slot nor collided
+        assert_eq!(station.sent().len(), framed(10));
+        assert_eq!(station.nic().statistics().collisions.get(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_duplex_mismatch() {
+        let station = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Full));
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Auto);
+        station.connect(partner.clone()).unwrap();
+        assert_eq!(station.nic().duplex(), Duplex::Full);
+        assert_eq!(partner.nic().duplex(), Duplex::Half);
+
+        // The full duplex end sends into the half duplex end's frame after its slot time
+        let (ours, theirs) = tokio::join!(
+            async {
+                tokio::time::sleep(clock::byte_times(SLOT_SIZE + 100)).await;
+                station.send(100).await
+            },
+            partner.send(1000),
+        );
+        assert!(matches!(ours, Ok(TransmitStatus::Ok)));
+        assert!(matches!(theirs, Err(TransmitStatus::LateCollision)));
+        assert_eq!(station.nic().statistics().collisions.get(), 0);
+        assert_eq!(partner.nic().statistics().late_collisions.get(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_late_collision() {
+        let station = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        station.connect(partner.clone()).unwrap();
+        *station.interference.lock().unwrap() = SLOT_SIZE + 20..SLOT_SIZE + 24;
+
+        assert!(matches!(station.send(1000).await, Err(TransmitStatus::LateCollision)));
+        // Jammed and dropped instead of retransmitted
+        assert!(station.sent().len() < framed(1000));
+        assert!(station.sent().ends_with(&[JAM; JAM_SIZE]));
+        let statistics = station.nic().statistics();
+        assert_eq!(statistics.collisions.get(), 1);
+        assert_eq!(statistics.late_collisions.get(), 1);
+        assert_eq!(statistics.frames_out.get(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slotted_aloha_slot_boundary() {
+        let station = TestStation::new(AccessMethod::SlottedAloha, DuplexSetting::Forced(Duplex::Half));
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        station.connect(partner.clone()).unwrap();
+
+        tokio::time::sleep(clock::byte_times(100)).await;
+        assert!(station.send(100).await.is_ok());
+        let (start, _) = station.sent.lock().unwrap()[0];
+        let into_slot = start.as_nanos() % clock::byte_times(ALOHA_SLOT_SIZE).as_nanos();
+        assert!(into_slot <= clock::BYTE_TIME.as_nanos(), "{start:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_jam() {
+        let station = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        station.connect(partner.clone()).unwrap();
+        *station.interference.lock().unwrap() = 20..24;
+
+        assert!(matches!(station.send(100).await, Ok(TransmitStatus::Ok)));
+        // The aborted prefix is followed by the jam, then the frame is sent again in full
+        let sent = station.sent();
+        let frame = DELIMITER_SIZE + SLOT_SIZE;
+        let aborted = sent.len() - JAM_SIZE - frame;
+        assert!((20..24).contains(&aborted), "{aborted}");
+        assert_eq!(sent[aborted..aborted + JAM_SIZE], [JAM; JAM_SIZE]);
+        assert_eq!(sent[..aborted], sent[aborted + JAM_SIZE..][..aborted]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_carrier_extension() {
+        let station = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        station.connect(partner.clone()).unwrap();
+
+        assert!(station.send(10).await.is_ok());
+        let sent = station.sent();
+        assert_eq!(sent.len(), DELIMITER_SIZE + SLOT_SIZE);
+        assert!(sent[framed(10)..].iter().all(|byte| *byte == EXTENSION));
+
+        // The extension holds the carrier up, but the partner only receives the frame's octets
+        let received = partner.receive_frame().await.unwrap();
+        assert_eq!(received.data[..10], [0x42; 10]);
+        assert_eq!(partner.nic().link_statistics().unwrap().octets_in, framed(10) as u64);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_limit() {
+        let station = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        station.connect(partner.clone()).unwrap();
+
+        let frame = framed(1500);
+        assert!(station.send(1500).await.is_ok());
+        let mut burst = frame;
+        while burst < BURST_LIMIT {
+            let before = station.sent().len();
+            assert!(station.send(1500).await.is_ok());
+            // Continues the burst: the gap is filled with extension
+            let sent = station.sent();
+            assert_eq!(sent.len() - before, IFS + frame);
+            assert_eq!(sent[before..before + IFS], [EXTENSION; IFS]);
+            burst += IFS + frame;
+        }
+
+        let before = station.sent().len();
+        assert!(station.send(1500).await.is_ok());
+        let sent = station.sent.lock().unwrap().clone();
+        assert_eq!(sent.len() - before, frame);
+        assert_eq!(sent[before].1, PREAMBLE[0]);
+        // Deferred for the interframe gap
+        assert!(sent[before].0 - sent[before - 1].0 >= clock::byte_times(IFS));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deference() {
+        let station = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        station.connect(partner.clone()).unwrap();
+
+        // The carrier is sensed until the partner's bytes have been read
+        let start = clock::now();
+        for _ in 0..10 {
+            partner.nic().transmit(0x42).await;
+        }
+        let reader = station.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(clock::byte_times(50)).await;
+            while reader.nic().recieve().await.is_some() {}
+        });
+
+        assert!(station.send(10).await.is_ok());
+        let (first, _) = station.sent.lock().unwrap()[0];
+        assert!(first >= start + clock::byte_times(50 + IFS), "{first:?}");
+    }
+
+    #[tokio::test]
+    async fn test_burst_runt() {
+        let station = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Forced(Duplex::Half));
+        let runt = TestStation::fcs(&Vec::new()).to_le_bytes().to_vec();
+        station.receive_state().await.copied.push_back(runt);
+
+        assert!(matches!(station.receive_frame().await, Err(ReceiveError::Runt(CRC_SIZE))));
+        assert_eq!(station.nic().statistics().runts.get(), 1);
+    }
+
+    #[test]
+    fn test_padding_not_delimiter() {
+        let station = TestStation::default();
+        let (dest, src) = (MacAddr::broadcast(), station.mac());
+        // Data of preamble bytes can still form a delimiter with the FCS, padding must not
+        for octets in 0..MIN_FRAME_SIZE {
+            for byte in (0..=u8::MAX).filter(|byte| *byte != PREAMBLE[0]) {
+                let frame = station.encapsulate_frame(&dest, &src, 0x0800, vec![byte; octets]);
+                let deframed = Framing::Preamble.deframe(&Framing::Preamble.frame(&frame));
+                assert_eq!(deframed, vec![Ok(frame)]);
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_frame_mode() {
+        let station = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Auto);
+        let partner = TestStation::new(AccessMethod::CsmaCd, DuplexSetting::Auto);
+        let properties = LinkProperties::frames(8_000_000, clock::byte_times(10));
+        station.connect_with(partner.clone(), properties).unwrap();
+
+        let (src, other) = (station.mac(), MacAddr::default());
+        let sent = station.transmit_frame(&other, &src, 0x0800, vec![0x41; 10]).await;
+        assert!(matches!(sent, Ok(TransmitStatus::Ok)));
+        let sent = station.transmit_frame(&partner.mac(), &src, 100, vec![0x42; 100]).await;
+        assert!(matches!(sent, Ok(TransmitStatus::Ok)));
+
+        // The frame for another station is filtered, the byte transmitter is never needed
+        let received = ReceivedFrame {
+            dest: partner.mac(),
+            src: src.clone(),
+            type_len: 100,
+            data: vec![0x42; 100],
+        };
+        assert_eq!(partner.receive_frame().await, Ok(received));
+        assert!(station.sent().is_empty());
+        assert_eq!(station.nic().statistics().frames_out.get(), 2);
+        assert_eq!(partner.nic().statistics().frames_in.get(), 1);
+
+        partner.disconnect().await.unwrap();
+        let sent = station.transmit_frame(&partner.mac(), &src, 0x0800, vec![0x42; 100]).await;
+        assert!(matches!(sent, Err(TransmitStatus::Physical(PhysicalError::LinkDown))));
+    }
+
+    #[test]
+    fn test_aloha_peak_throughput() {
+        let pure = AccessMethod::PureAloha.throughput(0.5).unwrap();
+        let slotted = AccessMethod::SlottedAloha.throughput(1.0).unwrap();
+        assert!((pure - 1.0 / (2.0 * std::f64::consts::E)).abs() < 1e-9);
+        assert!((slotted - 1.0 / std::f64::consts::E).abs() < 1e-9);
+        assert!(AccessMethod::SlottedAloha.throughput(0.5).unwrap() > pure);
+        assert_eq!(AccessMethod::CsmaCd.throughput(1.0), None);
+    }
+
+    #[test]
+    fn test_deframe_burst() {
+        // Extension carries no data, so the frames of a burst arrive back to back
+        let first = [vec![1, 2, 3], vec![EXTENSION; 20]].concat();
+        let second = vec![4, 5, 6];
+        let burst = [Framing::Preamble.frame(&first), Framing::Preamble.frame(&second)].concat();
+
+        let frames = Framing::Preamble.deframe(&burst);
+        assert_eq!(frames, vec![Ok(first), Ok(second)]);
+    }
+}
@@ -1,13 +1,23 @@
+mod collision_avoidance;
 mod error_control;
 mod flow_control;
+// No station of the demo runs on a point to point link, HDLC and PPP are exercised by their tests
+#[allow(dead_code)]
+mod hdlc;
 mod header;
+// The demo stations send Ethernet II frames, LLC and SNAP are exercised by their tests
+#[allow(dead_code)]
 mod logical_link_control;
 mod media_access_control;
+// Like HDLC, PPP only runs in its tests
+#[allow(dead_code)]
+mod ppp;
+mod protocols;
+mod token_passing;
 
 pub use error_control::ErrorControl;
 pub use flow_control::FlowControl;
-pub use logical_link_control::LogicalLinkControl;
-pub use media_access_control::{AccessControl, TransmitState, ReceiveState, ReceiveStatus};
+pub use media_access_control::{AccessControl, AccessMethod, TransmitState, ReceiveState, SLOT_SIZE};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MacAddr([u8; 6]);
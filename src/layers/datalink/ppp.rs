@@ -0,0 +1,621 @@
+/*
+  Reference:
+    RFC 1661 (The Point-to-Point Protocol)
+    RFC 1662 (PPP in HDLC-like Framing)
+    RFC 1332 (The PPP Internet Protocol Control Protocol)
+*/
+use super::error_control::ErrorControl;
+use super::hdlc;
+use crate::layers::physical::PhysicalLayer;
+use futures::Future;
+use std::collections::VecDeque;
+use std::net::Ipv4Addr;
+use tokio::sync::MutexGuard;
+use tracing::{debug, trace};
+
+/// All-stations address and unnumbered information control field of HDLC-like framing
+const ADDRESS: u8 = 0xFF;
+const CONTROL: u8 = 0x03;
+
+pub const LCP: u16 = 0xC021;
+pub const IPCP: u16 = 0x8021;
+pub const IPV4: u16 = 0x0021;
+
+const DEFAULT_MRU: u16 = 1500;
+const MIN_MRU: u16 = 64;
+
+// LCP configuration options
+const MAXIMUM_RECEIVE_UNIT: u8 = 1;
+const MAGIC_NUMBER: u8 = 5;
+
+// IPCP configuration options
+const IP_ADDRESS: u8 = 3;
+
+// Names as in RFC 1661
+#[allow(clippy::enum_variant_names)]
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    ConfigureRequest = 1,
+    ConfigureAck = 2,
+    ConfigureNak = 3,
+    ConfigureReject = 4,
+    TerminateRequest = 5,
+    TerminateAck = 6,
+    CodeReject = 7,
+    ProtocolReject = 8,
+    EchoRequest = 9,
+    EchoReply = 10,
+    DiscardRequest = 11,
+}
+
+impl Code {
+    const ALL: [Code; 11] = [
+        Code::ConfigureRequest,
+        Code::ConfigureAck,
+        Code::ConfigureNak,
+        Code::ConfigureReject,
+        Code::TerminateRequest,
+        Code::TerminateAck,
+        Code::CodeReject,
+        Code::ProtocolReject,
+        Code::EchoRequest,
+        Code::EchoReply,
+        Code::DiscardRequest,
+    ];
+
+    fn from_u8(code: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| *c as u8 == code)
+    }
+}
+
+/// A packet of LCP or a network control protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Packet {
+    code: u8,
+    identifier: u8,
+    data: Vec<u8>,
+}
+
+impl Packet {
+    fn to_bytes(&self) -> Vec<u8> {
+        let length = (4 + self.data.len()) as u16;
+        [&[self.code, self.identifier], length.to_be_bytes().as_ref(), &self.data].concat()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let length = u16::from_be_bytes([*bytes.get(2)?, *bytes.get(3)?]) as usize;
+        Some(Packet {
+            code: bytes[0],
+            identifier: bytes[1],
+            data: bytes.get(4..length)?.to_vec(),
+        })
+    }
+}
+
+/// A configuration option of LCP or IPCP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigOption {
+    pub kind: u8,
+    pub data: Vec<u8>,
+}
+
+fn encode_options(options: &[ConfigOption]) -> Vec<u8> {
+    options
+        .iter()
+        .flat_map(|option| [&[option.kind, option.data.len() as u8 + 2], option.data.as_slice()].concat())
+        .collect()
+}
+
+fn decode_options(mut bytes: &[u8]) -> Option<Vec<ConfigOption>> {
+    let mut options = Vec::new();
+    while !bytes.is_empty() {
+        let length = *bytes.get(1)? as usize;
+        if length < 2 {
+            return None;
+        }
+        options.push(ConfigOption {
+            kind: bytes[0],
+            data: bytes.get(2..length)?.to_vec(),
+        });
+        bytes = &bytes[length..];
+    }
+    Some(options)
+}
+
+/// Verdict on an option requested by the peer
+enum Verdict {
+    Ack,
+    /// Acceptable option type, but with another value
+    Nak(Vec<u8>),
+    Reject,
+}
+
+/// States of the option negotiation automaton, without the timer driven ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NegotiationState {
+    #[default]
+    Closed,
+    RequestSent,
+    AckReceived,
+    AckSent,
+    Opened,
+}
+
+/// Option negotiation of one control protocol.
+#[derive(Debug, Default)]
+pub struct Negotiation {
+    state: NegotiationState,
+    identifier: u8,
+    /// Options of our last Configure-Request
+    requested: Vec<ConfigOption>,
+}
+
+impl Negotiation {
+    pub fn state(&self) -> NegotiationState {
+        self.state
+    }
+
+    fn value(&self, kind: u8) -> Option<&[u8]> {
+        self.requested.iter().find(|o| o.kind == kind).map(|o| o.data.as_slice())
+    }
+}
+
+/// Phases of a PPP link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Phase {
+    #[default]
+    Dead,
+    /// LCP negotiates the link configuration
+    Establish,
+    /// Network control protocols negotiate, IPv4 flows once IPCP is opened
+    Network,
+    Terminate,
+}
+
+pub struct PppState {
+    phase: Phase,
+    lcp: Negotiation,
+    ipcp: Negotiation,
+    magic: u32,
+    /// Maximum receive unit of the peer
+    peer_mru: u16,
+    /// Our IPv4 address, unspecified to have it assigned by the peer
+    address: Ipv4Addr,
+    /// Address assigned to a peer that asks for one
+    peer_address: Option<Ipv4Addr>,
+    received: VecDeque<Vec<u8>>,
+}
+
+impl PppState {
+    pub fn new(address: Ipv4Addr, peer_address: Option<Ipv4Addr>) -> Self {
+        PppState {
+            phase: Phase::Dead,
+            lcp: Negotiation::default(),
+            ipcp: Negotiation::default(),
+            magic: rand::random::<u32>().max(1),
+            peer_mru: DEFAULT_MRU,
+            address,
+            peer_address,
+            received: VecDeque::new(),
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn lcp(&self) -> &Negotiation {
+        &self.lcp
+    }
+
+    pub fn ipcp(&self) -> &Negotiation {
+        &self.ipcp
+    }
+
+    pub fn address(&self) -> Ipv4Addr {
+        self.address
+    }
+
+    /// Whether IPv4 datagrams can be exchanged
+    pub fn is_open(&self) -> bool {
+        self.phase == Phase::Network && self.ipcp.state == NegotiationState::Opened
+    }
+
+    fn negotiation(&mut self, protocol: u16) -> &mut Negotiation {
+        match protocol {
+            LCP => &mut self.lcp,
+            _ => &mut self.ipcp,
+        }
+    }
+
+    fn initial_options(&self, protocol: u16) -> Vec<ConfigOption> {
+        match protocol {
+            LCP => vec![
+                ConfigOption {
+                    kind: MAXIMUM_RECEIVE_UNIT,
+                    data: DEFAULT_MRU.to_be_bytes().to_vec(),
+                },
+                ConfigOption {
+                    kind: MAGIC_NUMBER,
+                    data: self.magic.to_be_bytes().to_vec(),
+                },
+            ],
+            _ => vec![ConfigOption {
+                kind: IP_ADDRESS,
+                data: self.address.octets().to_vec(),
+            }],
+        }
+    }
+
+    fn review(&self, protocol: u16, option: &ConfigOption) -> Verdict {
+        match (protocol, option.kind, option.data.len()) {
+            (LCP, MAXIMUM_RECEIVE_UNIT, 2) => {
+                match u16::from_be_bytes([option.data[0], option.data[1]]) >= MIN_MRU {
+                    true => Verdict::Ack,
+                    false => Verdict::Nak(DEFAULT_MRU.to_be_bytes().to_vec()),
+                }
+            }
+            (LCP, MAGIC_NUMBER, 4) => {
+                let magic = u32::from_be_bytes(option.data[..].try_into().unwrap());
+                // The same magic number as ours suggests the link is looped back
+                match magic != 0 && magic != self.magic {
+                    true => Verdict::Ack,
+                    false => Verdict::Nak(rand::random::<u32>().max(1).to_be_bytes().to_vec()),
+                }
+            }
+            (IPCP, IP_ADDRESS, 4) => match (option.data == [0; 4], self.peer_address) {
+                (false, _) => Verdict::Ack,
+                (true, Some(address)) => Verdict::Nak(address.octets().to_vec()),
+                (true, None) => Verdict::Reject,
+            },
+            _ => Verdict::Reject,
+        }
+    }
+
+    /// Takes over the options the peer acknowledged
+    fn apply_ours(&mut self, protocol: u16) {
+        match protocol {
+            LCP => {
+                if let Some(magic) = self.lcp.value(MAGIC_NUMBER) {
+                    self.magic = u32::from_be_bytes(magic.try_into().unwrap());
+                }
+            }
+            _ => {
+                if let Some(address) = self.ipcp.value(IP_ADDRESS) {
+                    self.address = Ipv4Addr::from(<[u8; 4]>::try_from(address).unwrap());
+                }
+            }
+        }
+    }
+
+    /// Takes over the options of the peer we acknowledged
+    fn apply_theirs(&mut self, protocol: u16, options: &[ConfigOption]) {
+        if protocol != LCP {
+            return;
+        }
+        if let Some(mru) = options.iter().find(|o| o.kind == MAXIMUM_RECEIVE_UNIT) {
+            self.peer_mru = u16::from_be_bytes([mru.data[0], mru.data[1]]);
+        }
+    }
+
+    /// Starts the negotiation of a protocol and returns the Configure-Request to send
+    fn request(&mut self, protocol: u16) -> Packet {
+        let options = match self.negotiation(protocol).requested.is_empty() {
+            true => self.initial_options(protocol),
+            false => self.negotiation(protocol).requested.clone(),
+        };
+        let negotiation = self.negotiation(protocol);
+        negotiation.identifier = negotiation.identifier.wrapping_add(1);
+        negotiation.requested = options;
+        if negotiation.state != NegotiationState::AckSent {
+            negotiation.state = NegotiationState::RequestSent;
+        }
+        Packet {
+            code: Code::ConfigureRequest as u8,
+            identifier: negotiation.identifier,
+            data: encode_options(&negotiation.requested),
+        }
+    }
+
+    /// Handles a control protocol packet and returns the packets to send in response
+    fn handle(&mut self, protocol: u16, packet: Packet) -> Vec<(u16, Packet)> {
+        let reply = |code: Code, data: Vec<u8>| {
+            (
+                protocol,
+                Packet {
+                    code: code as u8,
+                    identifier: packet.identifier,
+                    data,
+                },
+            )
+        };
+
+        let mut responses = Vec::new();
+        match Code::from_u8(packet.code) {
+            Some(Code::ConfigureRequest) => {
+                let Some(options) = decode_options(&packet.data) else {
+                    return responses;
+                };
+                if self.negotiation(protocol).state == NegotiationState::Closed {
+                    // Passive open: negotiate our own options as well
+                    if protocol == LCP {
+                        self.phase = Phase::Establish;
+                    }
+                    responses.push((protocol, self.request(protocol)));
+                }
+
+                let mut naks = Vec::new();
+                let mut rejects = Vec::new();
+                for option in options.iter() {
+                    match self.review(protocol, option) {
+                        Verdict::Ack => (),
+                        Verdict::Nak(data) => naks.push(ConfigOption { kind: option.kind, data }),
+                        Verdict::Reject => rejects.push(option.clone()),
+                    }
+                }
+
+                if !rejects.is_empty() {
+                    responses.push(reply(Code::ConfigureReject, encode_options(&rejects)));
+                } else if !naks.is_empty() {
+                    responses.push(reply(Code::ConfigureNak, encode_options(&naks)));
+                } else {
+                    self.apply_theirs(protocol, &options);
+                    let negotiation = self.negotiation(protocol);
+                    negotiation.state = match negotiation.state {
+                        NegotiationState::AckReceived | NegotiationState::Opened => NegotiationState::Opened,
+                        _ => NegotiationState::AckSent,
+                    };
+                    responses.push(reply(Code::ConfigureAck, packet.data.clone()));
+                }
+            }
+            Some(Code::ConfigureAck) => {
+                let negotiation = self.negotiation(protocol);
+                if packet.identifier == negotiation.identifier {
+                    negotiation.state = match negotiation.state {
+                        NegotiationState::AckSent => NegotiationState::Opened,
+                        _ => NegotiationState::AckReceived,
+                    };
+                    self.apply_ours(protocol);
+                }
+            }
+            Some(code @ (Code::ConfigureNak | Code::ConfigureReject)) => {
+                let Some(options) = decode_options(&packet.data) else {
+                    return responses;
+                };
+                let negotiation = self.negotiation(protocol);
+                if packet.identifier != negotiation.identifier {
+                    return responses;
+                }
+                for option in options {
+                    let requested = negotiation.requested.iter().position(|o| o.kind == option.kind);
+                    match (code, requested) {
+                        (Code::ConfigureNak, Some(i)) => negotiation.requested[i] = option,
+                        (Code::ConfigureReject, Some(i)) => {
+                            negotiation.requested.remove(i);
+                        }
+                        _ => (),
+                    }
+                }
+                debug!(protocol, ?code, "options renegotiated");
+                responses.push((protocol, self.request(protocol)));
+            }
+            Some(Code::TerminateRequest) => {
+                self.close(protocol);
+                responses.push(reply(Code::TerminateAck, Vec::new()));
+            }
+            Some(Code::TerminateAck) => self.close(protocol),
+            Some(Code::EchoRequest) if protocol == LCP => {
+                responses.push(reply(Code::EchoReply, self.magic.to_be_bytes().to_vec()));
+            }
+            Some(_) => (),
+            None => responses.push(reply(Code::CodeReject, packet.to_bytes())),
+        }
+
+        if protocol == LCP && self.lcp.state == NegotiationState::Opened && self.phase == Phase::Establish {
+            debug!(peer_mru = self.peer_mru, "LCP opened");
+            self.phase = Phase::Network;
+            responses.push((IPCP, self.request(IPCP)));
+        }
+        responses
+    }
+
+    fn close(&mut self, protocol: u16) {
+        self.negotiation(protocol).state = NegotiationState::Closed;
+        if protocol == LCP {
+            self.phase = Phase::Dead;
+            self.lcp = Negotiation::default();
+            self.ipcp = Negotiation::default();
+        }
+    }
+}
+
+/// The Point-to-Point Protocol in HDLC-like framing.
+///
+/// The station is driven by `ppp_service`, which handles the packets received since the last
+/// call. LCP establishes the link, then IPCP negotiates the IPv4 addresses.
+pub trait PointToPoint: PhysicalLayer + ErrorControl {
+    fn ppp_state(&self) -> impl Future<Output = MutexGuard<'_, PppState>>;
+
+    /// Starts link establishment
+    async fn ppp_open(&self) {
+        let packet = {
+            let mut state = self.ppp_state().await;
+            state.phase = Phase::Establish;
+            state.request(LCP)
+        };
+        send_packets(self, vec![(LCP, packet)]).await;
+    }
+
+    /// Terminates the link
+    async fn ppp_close(&self) {
+        let packet = {
+            let mut state = self.ppp_state().await;
+            state.phase = Phase::Terminate;
+            state.lcp.identifier = state.lcp.identifier.wrapping_add(1);
+            Packet {
+                code: Code::TerminateRequest as u8,
+                identifier: state.lcp.identifier,
+                data: Vec::new(),
+            }
+        };
+        send_packets(self, vec![(LCP, packet)]).await;
+    }
+
+    /// Sends an IPv4 datagram, returns `false` if the network layer is not open
+    async fn ppp_send(&self, datagram: Vec<u8>) -> bool {
+        let mru = {
+            let state = self.ppp_state().await;
+            if !state.is_open() {
+                return false;
+            }
+            state.peer_mru as usize
+        };
+        if datagram.len() > mru {
+            debug!(octets = datagram.len(), mru, "datagram exceeds the peer's MRU");
+            return false;
+        }
+        send_protocol(self, IPV4, datagram).await;
+        true
+    }
+
+    async fn ppp_receive(&self) -> Option<Vec<u8>> {
+        self.ppp_state().await.received.pop_front()
+    }
+
+    /// Handles the frames received since the last call
+    async fn ppp_service(&self) {
+        for frame in hdlc::receive(self).await {
+            if frame.len() < 4 || frame[0] != ADDRESS || frame[1] != CONTROL {
+                continue;
+            }
+            let protocol = u16::from_be_bytes([frame[2], frame[3]]);
+            let information = &frame[4..];
+            let responses = {
+                let mut state = self.ppp_state().await;
+                match protocol {
+                    LCP | IPCP => match Packet::from_bytes(information) {
+                        Some(packet) => {
+                            trace!(protocol, code = packet.code, identifier = packet.identifier, "packet received");
+                            state.handle(protocol, packet)
+                        }
+                        None => Vec::new(),
+                    },
+                    IPV4 if state.is_open() => {
+                        state.received.push_back(information.to_vec());
+                        Vec::new()
+                    }
+                    _ if state.lcp.state == NegotiationState::Opened => {
+                        state.lcp.identifier = state.lcp.identifier.wrapping_add(1);
+                        let packet = Packet {
+                            code: Code::ProtocolReject as u8,
+                            identifier: state.lcp.identifier,
+                            data: frame[2..].to_vec(),
+                        };
+                        vec![(LCP, packet)]
+                    }
+                    _ => Vec::new(),
+                }
+            };
+            send_packets(self, responses).await;
+        }
+    }
+}
+
+async fn send_protocol<T: PointToPoint + ?Sized>(station: &T, protocol: u16, information: Vec<u8>) {
+    let frame = [&[ADDRESS, CONTROL], protocol.to_be_bytes().as_ref(), &information].concat();
+    hdlc::send(station, frame).await;
+}
+
+async fn send_packets<T: PointToPoint + ?Sized>(station: &T, packets: Vec<(u16, Packet)>) {
+    for (protocol, packet) in packets {
+        trace!(protocol, code = packet.code, identifier = packet.identifier, "packet sent");
+        send_protocol(station, protocol, packet.to_bytes()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::physical::{Framing, LinkProperties};
+    use crate::layers::NIC;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    struct TestStation {
+        nic: NIC,
+        state: Mutex<PppState>,
+    }
+
+    impl TestStation {
+        fn new(address: Ipv4Addr, peer_address: Option<Ipv4Addr>) -> Self {
+            TestStation {
+                nic: NIC::default(),
+                state: Mutex::new(PppState::new(address, peer_address)),
+            }
+        }
+    }
+
+    impl PhysicalLayer for TestStation {
+        fn nic(&self) -> &NIC {
+            &self.nic
+        }
+    }
+
+    impl ErrorControl for TestStation {}
+
+    impl PointToPoint for TestStation {
+        async fn ppp_state(&self) -> MutexGuard<'_, PppState> {
+            self.state.lock().await
+        }
+    }
+
+    #[test]
+    fn test_options() {
+        let options = vec![
+            ConfigOption {
+                kind: MAXIMUM_RECEIVE_UNIT,
+                data: vec![0x05, 0xDC],
+            },
+            ConfigOption {
+                kind: IP_ADDRESS,
+                data: vec![10, 0, 0, 1],
+            },
+        ];
+        let bytes = encode_options(&options);
+        assert_eq!(bytes, [1, 4, 0x05, 0xDC, 3, 6, 10, 0, 0, 1]);
+        assert_eq!(decode_options(&bytes), Some(options));
+        assert_eq!(decode_options(&[1, 1]), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_negotiation() {
+        let server = Arc::new(TestStation::new(Ipv4Addr::new(10, 0, 0, 1), Some(Ipv4Addr::new(10, 0, 0, 2))));
+        let client = Arc::new(TestStation::new(Ipv4Addr::UNSPECIFIED, None));
+        let properties = LinkProperties {
+            framing: Framing::ByteStuffing,
+            ..Default::default()
+        };
+        server.connect_with(client.clone(), properties).unwrap();
+
+        client.ppp_open().await;
+        for _ in 0..5 {
+            server.ppp_service().await;
+            client.ppp_service().await;
+        }
+
+        assert!(server.ppp_state().await.is_open());
+        assert!(client.ppp_state().await.is_open());
+        assert_eq!(client.ppp_state().await.address(), Ipv4Addr::new(10, 0, 0, 2));
+
+        assert!(client.ppp_send(vec![0x45, 0x00]).await);
+        server.ppp_service().await;
+        assert_eq!(server.ppp_receive().await, Some(vec![0x45, 0x00]));
+
+        client.ppp_close().await;
+        server.ppp_service().await;
+        client.ppp_service().await;
+        assert_eq!(server.ppp_state().await.phase(), Phase::Dead);
+        assert_eq!(client.ppp_state().await.phase(), Phase::Dead);
+    }
+}
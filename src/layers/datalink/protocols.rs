@@ -0,0 +1,134 @@
+use super::{header::EtherType, MacAddr};
+use std::collections::HashMap;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::trace;
+
+/// OUI of a SNAP header whose protocol is an EtherType
+pub const ETHERTYPE_OUI: [u8; 3] = [0; 3];
+
+/// A protocol above the MAC, identified by the EtherType of its frames or by the LLC SAP its PDUs
+/// are sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    EtherType(EtherType),
+    Sap(u8),
+    /// A protocol behind a SNAP header of an OUI other than `ETHERTYPE_OUI`
+    Snap { oui: [u8; 3], protocol: u16 },
+}
+
+impl From<EtherType> for Protocol {
+    fn from(ether_type: EtherType) -> Self {
+        Protocol::EtherType(ether_type)
+    }
+}
+
+impl Protocol {
+    /// The protocol behind a SNAP header, a SNAP header of the EtherType OUI carries an EtherType
+    pub fn snap(oui: [u8; 3], protocol: u16) -> Self {
+        match oui {
+            ETHERTYPE_OUI => Protocol::EtherType(EtherType::from(protocol)),
+            _ => Protocol::Snap { oui, protocol },
+        }
+    }
+}
+
+/// A decapsulated frame handed to the protocol registered for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub destination: MacAddr,
+    pub source: MacAddr,
+    /// SAP the payload was sent from, if it was carried in an LLC PDU
+    pub ssap: Option<u8>,
+    pub payload: Vec<u8>,
+}
+
+/// Handlers of the protocols above the MAC.
+///
+/// A protocol registers and receives the payloads of its frames on the channel it gets back, so
+/// new protocols need no changes to the MAC or the LLC.
+#[derive(Debug, Clone, Default)]
+pub struct ProtocolRegistry {
+    handlers: HashMap<Protocol, UnboundedSender<Delivery>>,
+}
+
+impl ProtocolRegistry {
+    /// Registers a handler, replacing any earlier one for the same protocol
+    pub fn register(&mut self, protocol: impl Into<Protocol>) -> UnboundedReceiver<Delivery> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.handlers.insert(protocol.into(), tx);
+        rx
+    }
+
+    pub fn unregister(&mut self, protocol: impl Into<Protocol>) {
+        self.handlers.remove(&protocol.into());
+    }
+
+    pub fn is_registered(&self, protocol: impl Into<Protocol>) -> bool {
+        self.handlers.contains_key(&protocol.into())
+    }
+
+    /// Hands a payload to the handler of `protocol`, returns `false` if there is none
+    ///
+    /// A handler whose receiver was dropped is unregistered.
+    pub fn dispatch(&mut self, protocol: impl Into<Protocol>, delivery: Delivery) -> bool {
+        let protocol = protocol.into();
+        let Some(handler) = self.handlers.get(&protocol) else {
+            return false;
+        };
+        if handler.send(delivery).is_err() {
+            trace!(?protocol, "handler gone");
+            self.handlers.remove(&protocol);
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ether_type() {
+        assert_eq!(EtherType::from(0x0800), EtherType::IPv4);
+        assert_eq!(EtherType::from(0x88CC), EtherType::Unknown(0x88CC));
+        assert_eq!(u16::from(EtherType::Unknown(0x88CC)), 0x88CC);
+        assert_eq!(EtherType::from_type_len(0x86DD), Some(EtherType::IPv6));
+        assert_eq!(EtherType::from_type_len(46), None);
+        assert_eq!(EtherType::Unknown(0x0800), EtherType::IPv4);
+        assert_ne!(EtherType::Unknown(0x0801), EtherType::IPv4);
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let delivery = Delivery {
+            destination: MacAddr::broadcast(),
+            source: MacAddr::from([0x02, 0, 0, 0, 0, 1]),
+            ssap: None,
+            payload: vec![0x45, 0x00],
+        };
+        let mut registry = ProtocolRegistry::default();
+        let mut ipv4 = registry.register(EtherType::IPv4);
+
+        // Unknown(0x0800) is the same protocol as IPv4
+        assert!(registry.dispatch(EtherType::Unknown(0x0800), delivery.clone()));
+        assert_eq!(ipv4.try_recv(), Ok(delivery.clone()));
+        assert!(!registry.dispatch(EtherType::Arp, delivery.clone()));
+
+        drop(ipv4);
+        assert!(!registry.dispatch(EtherType::IPv4, delivery));
+        assert!(!registry.is_registered(EtherType::IPv4));
+    }
+
+    #[test]
+    fn test_snap() {
+        assert_eq!(Protocol::snap(ETHERTYPE_OUI, 0x0800), Protocol::EtherType(EtherType::IPv4));
+        assert_eq!(
+            Protocol::snap([0x00, 0x00, 0x0C], 0x2000),
+            Protocol::Snap {
+                oui: [0x00, 0x00, 0x0C],
+                protocol: 0x2000
+            }
+        );
+    }
+}
@@ -0,0 +1,587 @@
+/*
+  Token passing medium access control
+
+  Reference:
+    IEEE 802.5 (Token Ring), Clause 4
+    IEEE 802.4 (Token Bus)
+*/
+use super::{
+    header::TypeLen,
+    media_access_control::{AccessControl, AccessMethod, TransmitStatus},
+    MacAddr,
+};
+use crate::utils::clock;
+use tracing::{debug, trace, warn};
+
+/// IEEE 802 local experimental EtherType used for the addressed token of a token bus
+pub const TOKEN_FRAME: TypeLen = 0x88B6;
+
+/// Maximum time in byte times a station may transmit after capturing the token
+const TOKEN_HOLDING_TIME: usize = 5000;
+
+/// Time in byte times without any activity after which the active monitor assumes the token is lost
+const LOST_TOKEN_TIMEOUT: usize = 2 * TOKEN_HOLDING_TIME;
+
+/// Time in byte times a queued frame waits for the token before it is dropped
+const TRANSMIT_TIMEOUT: usize = 2 * LOST_TOKEN_TIMEOUT;
+
+/// The access control byte that precedes every frame on a token passing network: `PPP T M RRR`
+///
+/// The byte is not covered by the frame check sequence, so stations may change the monitor
+/// and reservation bits of a frame while repeating it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AccessControlField {
+    pub priority: u8,
+    /// Set on frames, clear on the token
+    pub frame: bool,
+    /// Set by the active monitor on frames passing it, to detect orphaned frames
+    pub monitor: bool,
+    pub reservation: u8,
+}
+
+impl AccessControlField {
+    pub fn token(priority: u8) -> Self {
+        AccessControlField {
+            priority,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<u8> for AccessControlField {
+    fn from(byte: u8) -> Self {
+        AccessControlField {
+            priority: byte >> 5,
+            frame: byte & 0b0001_0000 != 0,
+            monitor: byte & 0b0000_1000 != 0,
+            reservation: byte & 0b0000_0111,
+        }
+    }
+}
+
+impl From<AccessControlField> for u8 {
+    fn from(ac: AccessControlField) -> Self {
+        (ac.priority & 0b111) << 5
+            | (ac.frame as u8) << 4
+            | (ac.monitor as u8) << 3
+            | (ac.reservation & 0b111)
+    }
+}
+
+/// Reads the bytes sent while a carrier is sensed and returns the frames delimited in them
+async fn read_frames<T: AccessControl + ?Sized>(station: &T) -> Vec<Vec<u8>> {
+    let bytes = station.read_carrier().await;
+    station.deframe(&bytes).into_iter().filter(|frame| !frame.is_empty()).collect()
+}
+
+/// Destination and source of an encapsulated frame
+fn addresses(frame: &[u8]) -> Option<(MacAddr, MacAddr)> {
+    if frame.len() < 14 {
+        return None;
+    }
+    let mut dest = [0; 6];
+    let mut src = [0; 6];
+    dest.copy_from_slice(&frame[0..6]);
+    src.copy_from_slice(&frame[6..12]);
+    Some((MacAddr::from(dest), MacAddr::from(src)))
+}
+
+/// Transmits queued frames until the queue is empty or the token holding time would be exceeded,
+/// returns the number of frames sent
+async fn hold_token<T: AccessControl + ?Sized>(station: &T, priority: u8) -> usize {
+    let ac = AccessControlField {
+        priority,
+        frame: true,
+        ..Default::default()
+    };
+    let mut held = 0;
+    let mut sent = 0;
+    loop {
+        let mut state = station.transmit_state().await;
+        let Some((_, frame)) = state.token_queue.front() else {
+            break;
+        };
+        if held > 0 && held + frame.len() > TOKEN_HOLDING_TIME {
+            break;
+        }
+        let (ticket, frame) = state.token_queue.pop_front().unwrap();
+        drop(state);
+
+        held += frame.len();
+        trace!(priority, octets = frame.len(), "frame sent with token");
+        station.send_frame(&[&[u8::from(ac)], frame.as_slice()].concat()).await;
+        station.transmit_state().await.token_sent = ticket;
+        sent += 1;
+    }
+    sent
+}
+
+/// Passes the token on: around the ring, or to the successor on a token bus
+async fn release_token<T: AccessControl + ?Sized>(station: &T, method: &AccessMethod, priority: u8) {
+    let token = u8::from(AccessControlField::token(priority));
+    debug!(priority, "token released");
+    match method {
+        AccessMethod::TokenBus { successor, .. } => {
+            let frame = station.encapsulate_frame(successor, &station.mac(), TOKEN_FRAME, Vec::new());
+            station.send_frame(&[&[token], frame.as_slice()].concat()).await;
+        }
+        _ => station.send_frame(&[token]).await,
+    }
+}
+
+/// Uses a captured token on a ring.
+///
+/// The new token is only released once the frames sent have come back around the ring, so the
+/// reservations made in them by the other stations can be served.
+async fn capture_ring_token<T: AccessControl + ?Sized>(
+    station: &T,
+    method: &AccessMethod,
+    ac: AccessControlField,
+) {
+    match hold_token(station, ac.priority).await {
+        0 => release_ring_token(station, method, ac).await,
+        sent => station.transmit_state().await.circulating = Some((ac, sent)),
+    }
+}
+
+/// Releases the token captured on a ring.
+///
+/// A pending reservation above the token's priority raises the priority of the released token,
+/// the station remembers the old priority so it can lower it again once the reservation is served.
+async fn release_ring_token<T: AccessControl + ?Sized>(
+    station: &T,
+    method: &AccessMethod,
+    ac: AccessControlField,
+) {
+    let priority = if ac.reservation > ac.priority {
+        station.transmit_state().await.stacked = Some((ac.priority, ac.reservation));
+        ac.reservation
+    } else {
+        ac.priority
+    };
+    release_token(station, method, priority).await;
+}
+
+async fn ring_frame<T: AccessControl + ?Sized>(
+    station: &T,
+    method: &AccessMethod,
+    priority: u8,
+    monitor: bool,
+    frame: &[u8],
+) {
+    let mut ac = AccessControlField::from(frame[0]);
+    let body = &frame[1..];
+    let pending = !station.transmit_state().await.token_queue.is_empty();
+
+    if !ac.frame {
+        if station.transmit_state().await.circulating.take().is_some() {
+            debug!("token seen before own frames returned");
+        }
+        let stacked = station.transmit_state().await.stacked;
+        if let Some((old, new)) = stacked {
+            if ac.priority == new && ac.reservation <= old && !pending {
+                station.transmit_state().await.stacked = None;
+                release_token(station, method, old.max(ac.reservation)).await;
+                return;
+            }
+        }
+
+        if pending && priority >= ac.priority {
+            debug!(priority = ac.priority, reservation = ac.reservation, "token captured");
+            capture_ring_token(station, method, ac).await;
+            return;
+        }
+    } else {
+        if monitor {
+            if ac.monitor {
+                // The frame has been around the ring once already, its sender is gone
+                warn!("orphaned frame purged");
+                let mut state = station.transmit_state().await;
+                state.stacked = None;
+                state.circulating = None;
+                drop(state);
+                release_token(station, method, 0).await;
+                return;
+            }
+            ac.monitor = true;
+        }
+
+        let Some((dest, src)) = addresses(body) else {
+            return;
+        };
+        if src == station.mac() {
+            // Strip our own frame once it has circulated the ring, the last one releases the token
+            let mut state = station.transmit_state().await;
+            let Some((mut token, sent)) = state.circulating.take() else {
+                return;
+            };
+            token.reservation = token.reservation.max(ac.reservation);
+            if sent > 1 {
+                state.circulating = Some((token, sent - 1));
+                return;
+            }
+            drop(state);
+            release_ring_token(station, method, token).await;
+            return;
+        }
+        if station.recognize_address(&dest) {
+            station.receive_state().await.copied.push_back(body.to_vec());
+        }
+    }
+
+    if pending && priority > ac.reservation {
+        ac.reservation = priority;
+    }
+    station.send_frame(&[&[u8::from(ac)], body].concat()).await;
+}
+
+async fn bus_frame<T: AccessControl + ?Sized>(station: &T, method: &AccessMethod, frame: &[u8]) {
+    let ac = AccessControlField::from(frame[0]);
+    let body = &frame[1..];
+    let Some((dest, src)) = addresses(body) else {
+        return;
+    };
+    if src == station.mac() {
+        return;
+    }
+
+    if !ac.frame {
+        if dest == station.mac() {
+            debug!("token captured");
+            hold_token(station, 0).await;
+            release_token(station, method, 0).await;
+        }
+    } else if station.recognize_address(&dest) {
+        station.receive_state().await.copied.push_back(body.to_vec());
+    }
+}
+
+/// Runs the token passing MAC of a station.
+///
+/// Repeats frames around a ring, copies frames addressed to the station for `receive_frame` and
+/// transmits the frames queued by `transmit_frame` when the token is captured. The active
+/// monitor removes orphaned frames and issues a new token when none has been seen for a while.
+pub async fn run<T: AccessControl + ?Sized>(station: &T) {
+    let mut last_activity = clock::now();
+    loop {
+        let method = station.access_method();
+        let monitor = match method {
+            AccessMethod::TokenRing { monitor, .. } | AccessMethod::TokenBus { monitor, .. } => monitor,
+            _ => return,
+        };
+
+        let frames = read_frames(station).await;
+        if frames.is_empty() {
+            let lost = clock::now().saturating_sub(last_activity) > clock::byte_times(LOST_TOKEN_TIMEOUT);
+            if monitor && lost {
+                warn!("token lost");
+                let mut state = station.transmit_state().await;
+                state.stacked = None;
+                state.circulating = None;
+                drop(state);
+                if let AccessMethod::TokenBus { .. } = method {
+                    hold_token(station, 0).await;
+                }
+                release_token(station, &method, 0).await;
+                last_activity = clock::now();
+            }
+            tokio::time::sleep(clock::BYTE_TIME).await;
+            continue;
+        }
+
+        last_activity = clock::now();
+        for frame in frames {
+            match method {
+                AccessMethod::TokenRing { priority, .. } => {
+                    ring_frame(station, &method, priority, monitor, &frame).await
+                }
+                _ => bus_frame(station, &method, &frame).await,
+            }
+        }
+    }
+}
+
+/// Queues an encapsulated frame and waits until it has been sent with the token
+///
+/// A frame still queued after `TRANSMIT_TIMEOUT` is dropped.
+pub async fn transmit<T: AccessControl + ?Sized>(
+    station: &T,
+    frame: Vec<u8>,
+) -> Result<TransmitStatus, TransmitStatus> {
+    let ticket = {
+        let mut state = station.transmit_state().await;
+        state.token_ticket += 1;
+        let ticket = state.token_ticket;
+        state.token_queue.push_back((ticket, frame));
+        ticket
+    };
+    let deadline = clock::now() + clock::byte_times(TRANSMIT_TIMEOUT);
+    loop {
+        let mut state = station.transmit_state().await;
+        if state.token_sent >= ticket {
+            return Ok(TransmitStatus::Ok);
+        }
+        if clock::now() >= deadline {
+            if let Some(queued) = state.token_queue.iter().position(|(queued, _)| *queued == ticket) {
+                state.token_queue.remove(queued);
+                return Err(TransmitStatus::NoToken);
+            }
+        }
+        drop(state);
+        tokio::time::sleep(clock::BYTE_TIME).await;
+    }
+}
+
+/// Waits for the next frame copied off the network by `run`
+pub async fn receive<T: AccessControl + ?Sized>(station: &T) -> Vec<u8> {
+    loop {
+        if let Some(frame) = station.receive_state().await.copied.pop_front() {
+            return frame;
+        }
+        tokio::time::sleep(clock::BYTE_TIME).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::{bus::Bus, ring::Ring, segment::Segment};
+    use crate::layers::datalink::{
+        media_access_control::{ReceiveState, TransmitState},
+        ErrorControl,
+    };
+    use crate::layers::{PhysicalLayer, NIC};
+    use crate::utils::Simulateable;
+    use std::sync::Arc;
+    use tokio::sync::{Mutex, MutexGuard};
+    use tokio::task::JoinHandle;
+
+    #[derive(Default)]
+    struct TestStation {
+        nic: NIC,
+        transmit_state: Mutex<TransmitState>,
+        receive_state: Mutex<ReceiveState>,
+        /// Sources of the frames passed to the MAC client, in order
+        received: std::sync::Mutex<Vec<MacAddr>>,
+        passer: std::sync::Mutex<Option<JoinHandle<()>>>,
+    }
+
+    impl PhysicalLayer for TestStation {
+        fn nic(&self) -> &NIC {
+            &self.nic
+        }
+    }
+
+    impl ErrorControl for TestStation {}
+
+    impl AccessControl for TestStation {
+        async fn transmit_state(&self) -> MutexGuard<'_, TransmitState> {
+            self.transmit_state.lock().await
+        }
+
+        async fn receive_state(&self) -> MutexGuard<'_, ReceiveState> {
+            self.receive_state.lock().await
+        }
+    }
+
+    /// Starts the token passer of a station
+    fn start_passer(station: &Arc<TestStation>) {
+        let passer = station.clone();
+        *station.passer.lock().unwrap() = Some(tokio::spawn(async move { passer.token_passer().await }));
+    }
+
+    /// Starts the processes of a connected station and a MAC client that records what it receives
+    fn start(station: &Arc<TestStation>) {
+        start_passer(station);
+        let transmitter = station.clone();
+        tokio::spawn(async move { transmitter.byte_transmitter().await });
+        let client = station.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Ok(frame) = client.receive_frame().await {
+                    client.received.lock().unwrap().push(frame.src);
+                }
+            }
+        });
+    }
+
+    /// Connects stations with the given access methods to a running ring, in order
+    fn ring(methods: &[AccessMethod]) -> Vec<Arc<TestStation>> {
+        let ring = Arc::new(Ring::new(methods.len()).unwrap());
+        let stations: Vec<Arc<TestStation>> = methods.iter().map(|_| Default::default()).collect();
+        for (station, method) in stations.iter().zip(methods) {
+            station.nic().set_access_method(method.clone());
+            ring.connect(station.clone()).unwrap();
+            start(station);
+        }
+        tokio::spawn(async move {
+            loop {
+                ring.tick().await;
+                tokio::time::sleep(clock::BYTE_TIME).await;
+            }
+        });
+        stations
+    }
+
+    /// Connects `n` token bus stations to a running bus of two segments, the first one is the
+    /// active monitor. The token passes from each station to the next, the last one passes it back.
+    fn bus(n: usize) -> Vec<Arc<TestStation>> {
+        let bus = Arc::new(Bus::new([Segment::new(n - n / 2, 100.0), Segment::new(n / 2, 100.0)]).unwrap());
+        let stations: Vec<Arc<TestStation>> = (0..n).map(|_| Default::default()).collect();
+        for (i, station) in stations.iter().enumerate() {
+            let successor = stations[(i + 1) % n].mac();
+            station.nic().set_access_method(AccessMethod::TokenBus { successor, monitor: i == 0 });
+            bus.connect(station.clone()).unwrap();
+            start(station);
+        }
+        tokio::spawn(async move {
+            loop {
+                bus.tick().await;
+                tokio::time::sleep(clock::BYTE_TIME).await;
+            }
+        });
+        stations
+    }
+
+    fn station(priority: u8, monitor: bool) -> AccessMethod {
+        AccessMethod::TokenRing { priority, monitor }
+    }
+
+    async fn send(station: &TestStation, dest: &TestStation) -> Result<TransmitStatus, TransmitStatus> {
+        station.transmit_frame(&dest.mac(), &station.mac(), 0x0800, vec![0x42; 100]).await
+    }
+
+    /// Sources of the frames the station received, once `n` have arrived or a while has passed
+    async fn received(station: &TestStation, n: usize) -> Vec<MacAddr> {
+        let arrived = async {
+            while station.received.lock().unwrap().len() < n {
+                tokio::time::sleep(clock::BYTE_TIME).await;
+            }
+        };
+        let _ = tokio::time::timeout(clock::byte_times(1000), arrived).await;
+        station.received.lock().unwrap().clone()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_circulation() {
+        let stations = ring(&[station(0, true), station(0, false), station(0, false)]);
+
+        // The monitor issues the first token, which then visits every station in turn
+        for (i, sender) in stations.iter().enumerate() {
+            let receiver = &stations[(i + 1) % stations.len()];
+            assert!(matches!(send(sender, receiver).await, Ok(TransmitStatus::Ok)));
+        }
+        for (i, receiver) in stations.iter().enumerate() {
+            let sender = &stations[(i + stations.len() - 1) % stations.len()];
+            assert_eq!(received(receiver, 1).await, vec![sender.mac()]);
+            assert_eq!(receiver.nic().statistics().frames_out.get(), 1);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_lost_token() {
+        let stations = ring(&[station(0, true), station(0, false), station(0, false)]);
+        assert!(send(&stations[1], &stations[2]).await.is_ok());
+
+        // A station swallows whatever reaches it while its token passer is down
+        let stalled = &stations[2];
+        stalled.passer.lock().unwrap().take().unwrap().abort();
+        tokio::time::sleep(clock::byte_times(1000)).await;
+        while stalled.receive().await.is_some() {}
+        start_passer(stalled);
+
+        let (sender, receiver) = (stations[1].clone(), stations[0].clone());
+        let start = clock::now();
+        let exchange = tokio::spawn(async move { send(&sender, &receiver).await });
+        tokio::time::sleep(clock::byte_times(LOST_TOKEN_TIMEOUT / 2)).await;
+        assert!(!exchange.is_finished());
+
+        assert!(exchange.await.unwrap().is_ok());
+        assert!(clock::now() - start > clock::byte_times(LOST_TOKEN_TIMEOUT / 2));
+        assert_eq!(received(&stations[0], 1).await, vec![stations[1].mac()]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_token() {
+        // Without an active monitor no token is ever issued
+        let stations = ring(&[station(0, false), station(0, false)]);
+        let start = clock::now();
+        assert!(matches!(send(&stations[0], &stations[1]).await, Err(TransmitStatus::NoToken)));
+        assert!(clock::now() - start >= clock::byte_times(TRANSMIT_TIMEOUT));
+        assert!(stations[0].transmit_state().await.token_queue.is_empty());
+        assert_eq!(stations[0].nic().statistics().frames_out.get(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_priority_reservation() {
+        let stations = ring(&[station(0, true), station(0, false), station(0, false), station(5, false)]);
+        let (monitor, a, b, c) = (&stations[0], &stations[1], &stations[2], &stations[3]);
+
+        let mut exchanges = Vec::new();
+        for sender in [a, b, c] {
+            let (sender, receiver) = (sender.clone(), monitor.clone());
+            exchanges.push(tokio::spawn(async move { send(&sender, &receiver).await }));
+        }
+        for exchange in exchanges {
+            assert!(exchange.await.unwrap().is_ok());
+        }
+
+        // C reserves priority 5 in A's frame, so it gets the token before B, which is upstream of it
+        assert_eq!(received(monitor, 3).await, vec![a.mac(), c.mac(), b.mac()]);
+        assert_eq!(a.transmit_state().await.stacked, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_bus_circulation() {
+        let stations = bus(3);
+
+        // The monitor issues the first token, which is then addressed to each successor in turn
+        for (i, sender) in stations.iter().enumerate().rev() {
+            let receiver = &stations[(i + 1) % stations.len()];
+            assert!(matches!(send(sender, receiver).await, Ok(TransmitStatus::Ok)));
+        }
+        for (i, receiver) in stations.iter().enumerate() {
+            let sender = &stations[(i + stations.len() - 1) % stations.len()];
+            assert_eq!(received(receiver, 1).await, vec![sender.mac()]);
+            assert_eq!(receiver.nic().statistics().frames_out.get(), 1);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_bus_lost_token() {
+        let stations = bus(3);
+        assert!(send(&stations[1], &stations[2]).await.is_ok());
+
+        // The token is lost once it is addressed to a station whose token passer is down
+        let stalled = &stations[2];
+        stalled.passer.lock().unwrap().take().unwrap().abort();
+        tokio::time::sleep(clock::byte_times(1000)).await;
+        while stalled.receive().await.is_some() {}
+        start_passer(stalled);
+
+        let (sender, receiver) = (stations[1].clone(), stations[0].clone());
+        let start = clock::now();
+        let exchange = tokio::spawn(async move { send(&sender, &receiver).await });
+        tokio::time::sleep(clock::byte_times(LOST_TOKEN_TIMEOUT / 2)).await;
+        assert!(!exchange.is_finished());
+
+        // The monitor issues a new token to its successor
+        assert!(exchange.await.unwrap().is_ok());
+        assert!(clock::now() - start > clock::byte_times(LOST_TOKEN_TIMEOUT / 2));
+        assert_eq!(received(&stations[0], 1).await, vec![stations[1].mac()]);
+    }
+
+    #[test]
+    fn test_access_control_field() {
+        let ac = AccessControlField {
+            priority: 5,
+            frame: true,
+            monitor: false,
+            reservation: 3,
+        };
+        let byte = u8::from(ac);
+        assert_eq!(byte, 0b1011_0011);
+        assert_eq!(AccessControlField::from(byte), ac);
+        assert_eq!(u8::from(AccessControlField::token(0)), 0);
+    }
+}
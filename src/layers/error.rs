@@ -0,0 +1,82 @@
+use super::{physical::FramingError, MacAddr};
+use std::fmt;
+
+/// Errors of the physical layer and of the devices that connect NICs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysicalError {
+    /// Every port of the device is already connected
+    NoFreePort,
+    /// The device has no port with this number
+    NoSuchPort(usize),
+    /// Something is already plugged into this port
+    PortInUse(usize),
+    /// The NIC is not connected, or its link partner went away
+    LinkDown,
+    /// The link buffer is full, the octets were dropped
+    BufferOverflow(usize),
+    /// Whole frames can only be sent over a frame or signal mode link
+    ByteMode,
+    /// The link's mode differs from the mode of the links already attached to the device
+    ModeMismatch,
+    /// A link cannot carry anything without bandwidth
+    ZeroBandwidth,
+    /// A device needs at least one port
+    NoPorts,
+}
+
+/// Reasons a received frame is not passed to the MAC client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReceiveError {
+    /// The frame is addressed to another station
+    AddressFiltered(MacAddr),
+    /// The frame is shorter than the minimum frame size, usually a collision fragment
+    Runt(usize),
+    /// The frame exceeds the maximum frame size
+    TooLong(usize),
+    FcsMismatch,
+    /// The frame is not a whole number of octets
+    AlignmentError,
+    /// No frame could be delimited in the bytes received
+    Framing(FramingError),
+}
+
+impl From<FramingError> for ReceiveError {
+    fn from(error: FramingError) -> Self {
+        match error {
+            FramingError::NotOctetAligned => ReceiveError::AlignmentError,
+            _ => ReceiveError::Framing(error),
+        }
+    }
+}
+
+impl fmt::Display for PhysicalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhysicalError::NoFreePort => write!(f, "no free port"),
+            PhysicalError::NoSuchPort(port) => write!(f, "no port {}", port),
+            PhysicalError::PortInUse(port) => write!(f, "port {} in use", port),
+            PhysicalError::LinkDown => write!(f, "link down"),
+            PhysicalError::BufferOverflow(octets) => write!(f, "link buffer overflow, {} octets dropped", octets),
+            PhysicalError::ByteMode => write!(f, "frame sent on a byte mode link"),
+            PhysicalError::ModeMismatch => write!(f, "link mode differs from the device's links"),
+            PhysicalError::ZeroBandwidth => write!(f, "link without bandwidth"),
+            PhysicalError::NoPorts => write!(f, "device without ports"),
+        }
+    }
+}
+
+impl fmt::Display for ReceiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReceiveError::AddressFiltered(dest) => write!(f, "frame addressed to {}", dest),
+            ReceiveError::Runt(octets) => write!(f, "runt of {} octets", octets),
+            ReceiveError::TooLong(octets) => write!(f, "frame of {} octets too long", octets),
+            ReceiveError::FcsMismatch => write!(f, "frame check sequence mismatch"),
+            ReceiveError::AlignmentError => write!(f, "frame not a whole number of octets"),
+            ReceiveError::Framing(error) => write!(f, "framing error: {:?}", error),
+        }
+    }
+}
+
+impl std::error::Error for PhysicalError {}
+impl std::error::Error for ReceiveError {}
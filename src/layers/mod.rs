@@ -1,7 +1,10 @@
 mod datalink;
+mod error;
 mod nic;
 mod physical;
+mod statistics;
 
-pub use physical::{PhysicalLayer, Link};
-pub use datalink::{AccessControl, ErrorControl, MacAddr, TransmitState, ReceiveState, ReceiveStatus};
+pub use physical::{attach, Duplex, DuplexSetting, PhysicalLayer, LinkProperties};
+pub use datalink::{AccessControl, AccessMethod, ErrorControl, MacAddr, TransmitState, ReceiveState, SLOT_SIZE};
+pub use error::{PhysicalError, ReceiveError};
 pub use nic::NIC;
@@ -1,105 +1,249 @@
-use crate::run_async;
-
-use super::{physical::Link, MacAddr};
-use futures::FutureExt;
-use tokio::sync::{
-    mpsc::error::{TryRecvError, TrySendError},
-    RwLock,
+use super::{
+    physical::{Framing, Link, LinkMode, Symbol},
+    statistics::{LinkCounters, NicStatistics},
+    AccessMethod, Duplex, DuplexSetting, MacAddr, PhysicalError,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex, RwLock,
 };
+use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
+use tokio::time::Duration;
 
 /// Abstraction of a network interface card (NIC).
 ///
 /// Provides physical layer primitives for sending and receiving data.
 /// As well as Layer 2 primitives for addressing and switching.
+///
+/// The state is kept in atomics and in locks that are never held across an `.await`,
+/// so a NIC can be used from any runtime or driven synchronously.
+#[derive(Default)]
 pub struct NIC {
     mac: MacAddr,
-    transmitting: RwLock<bool>,
-    connection: RwLock<Option<Link>>,
+    access_method: RwLock<AccessMethod>,
+    duplex_setting: DuplexSetting,
+    /// Whether the resolved duplex mode is full duplex
+    full_duplex: AtomicBool,
+    transmitting: AtomicBool,
+    connection: Mutex<Option<Link>>,
+    /// MAC of the NIC at the other end of the connection
+    partner: Mutex<Option<MacAddr>>,
+    statistics: NicStatistics,
 }
 
-impl Default for NIC {
-    fn default() -> Self {
+impl NIC {
+    pub fn with_duplex(duplex_setting: DuplexSetting) -> Self {
         NIC {
-            mac: Default::default(),
-            transmitting: RwLock::new(false),
-            connection: RwLock::new(None),
+            duplex_setting,
+            ..Default::default()
         }
     }
-}
 
-impl NIC {
     pub fn mac(&self) -> MacAddr {
         self.mac.clone()
     }
 
+    pub fn access_method(&self) -> AccessMethod {
+        self.access_method.read().unwrap().clone()
+    }
+
+    pub fn set_access_method(&self, method: AccessMethod) {
+        *self.access_method.write().unwrap() = method;
+    }
+
+    pub fn duplex_setting(&self) -> DuplexSetting {
+        self.duplex_setting
+    }
+
+    /// The duplex mode resolved when the NIC was last connected
+    pub fn duplex(&self) -> Duplex {
+        match self.full_duplex.load(Ordering::Relaxed) {
+            true => Duplex::Full,
+            false => Duplex::Half,
+        }
+    }
+
+    pub fn set_duplex(&self, duplex: Duplex) {
+        self.full_duplex.store(duplex == Duplex::Full, Ordering::Relaxed);
+    }
+
     pub fn transmitting(&self) -> bool {
-        run_async!(self.transmitting.read().await.clone())
+        self.transmitting.load(Ordering::Acquire)
     }
 
     pub fn set_transmitting(&self, transmitting: bool) {
-        run_async!(
-            self.transmitting
-                .write()
-                .map(move |mut guard| *guard = transmitting)
-                .await
-        )
+        self.transmitting.store(transmitting, Ordering::Release);
+    }
+
+    /// Framing of the current connection
+    pub fn framing(&self) -> Framing {
+        self.with_link(|link| link.properties().framing).unwrap_or_default()
+    }
+
+    /// Mode of the current connection
+    pub fn mode(&self) -> LinkMode {
+        self.with_link(|link| link.properties().mode).unwrap_or_default()
     }
 
     pub fn set_connection(&self, connection: Option<Link>) {
-        run_async!(
-            self.connection
-                .write()
-                .map(move |mut guard| *guard = connection)
-                .await
-        )
+        if connection.is_none() {
+            *self.partner.lock().unwrap() = None;
+        }
+        *self.connection.lock().unwrap() = connection;
+    }
+
+    /// Connects the NIC to `partner` through its end of a link
+    pub fn attach(&self, link: Link, partner: MacAddr) {
+        *self.partner.lock().unwrap() = Some(partner);
+        *self.connection.lock().unwrap() = Some(link);
+        tracing::debug!(mac = %self.mac, "carrier up");
+    }
+
+    /// MAC of the NIC at the other end of the connection
+    pub fn partner(&self) -> Option<MacAddr> {
+        match self.is_connected() {
+            true => self.partner.lock().unwrap().clone(),
+            false => None,
+        }
     }
 
     pub fn is_receiving(&self) -> bool {
-        run_async!(self
-            .connection
-            .read()
-            .await
-            .as_ref()
-            .map_or(false, |conn| conn.is_recieving()))
+        self.is_connected() && self.with_link(Link::is_recieving).unwrap_or(false)
+    }
+
+    pub fn statistics(&self) -> &NicStatistics {
+        &self.statistics
+    }
+
+    /// Counters of the NIC's end of the current connection
+    pub fn link_statistics(&self) -> Option<LinkCounters> {
+        self.with_link(Link::statistics)
+    }
+
+    /// Inspects the NIC's end of the current connection
+    pub fn with_link<R>(&self, f: impl FnOnce(&Link) -> R) -> Option<R> {
+        self.connection.lock().unwrap().as_ref().map(f)
     }
 
+    /// Whether the NIC is connected, a link unplugged at the other end is dropped here as well
     pub fn is_connected(&self) -> bool {
-        run_async!(self.connection.read().map(|lock| lock.is_some()).await)
+        let mut handle = self.connection.lock().unwrap();
+        if handle.as_ref().is_some_and(|link| !link.is_up()) {
+            tracing::debug!(mac = %self.mac, "carrier lost");
+            *handle = None;
+        }
+        handle.is_some()
     }
 
     pub async fn transmit(&self, byte: u8) {
-        if let Some(conn) = self.connection.read().await.as_ref() {
+        let mut handle = self.connection.lock().unwrap();
+        if let Some(conn) = handle.as_ref() {
             let status = conn.send(byte);
-            match status {
-                Ok(()) => (),
-                Err(e) => match e {
-                    TrySendError::Closed(_) => {
-                        self.set_connection(None);
-                    }
-                    _ => (),
-                },
-            }
+            self.sent(&mut handle, status);
+        }
+    }
+
+    pub async fn transmit_symbol(&self, symbol: Symbol) {
+        let mut handle = self.connection.lock().unwrap();
+        if let Some(conn) = handle.as_ref() {
+            let status = conn.send_symbol(symbol);
+            self.sent(&mut handle, status);
         }
     }
 
+    fn sent<T: std::fmt::Debug>(&self, handle: &mut Option<Link>, status: Result<(), TrySendError<T>>) {
+        match status {
+            Ok(()) => (),
+            Err(e) => match e {
+                TrySendError::Closed(_) => {
+                    tracing::debug!(mac = %self.mac, "link down");
+                    *handle = None;
+                }
+                TrySendError::Full(data) => {
+                    tracing::warn!(mac = %self.mac, ?data, "link buffer overflow");
+                    self.statistics.buffer_overflows.increment();
+                }
+            },
+        }
+    }
+
+    /// Receives a byte of data, carrier extension is skipped
     pub async fn recieve(&self) -> Option<u8> {
-        let mut handle = self.connection.write().await;
-        if let Some(conn) = handle.as_mut() {
-            return match conn.recv() {
-                Ok(byte) => Some(byte),
-                Err(e) => {
-                    match e {
-                        TryRecvError::Disconnected => {
-                            drop(handle);
-                            self.set_connection(None);
-                        }
-                        _ => (),
+        let mut handle = self.connection.lock().unwrap();
+        let data = handle.as_mut()?.recv();
+        self.received(&mut handle, data)
+    }
+
+    pub async fn recieve_symbol(&self) -> Option<Symbol> {
+        let mut handle = self.connection.lock().unwrap();
+        let symbol = handle.as_mut()?.recv_symbol();
+        self.received(&mut handle, symbol)
+    }
+
+    fn received<T>(&self, handle: &mut Option<Link>, data: Result<T, TryRecvError>) -> Option<T> {
+        match data {
+            Ok(data) => Some(data),
+            Err(e) => {
+                match e {
+                    TryRecvError::Disconnected => {
+                        tracing::debug!(mac = %self.mac, "link down");
+                        *handle = None;
                     }
-                    None
+                    _ => (),
                 }
-            };
+                None
+            }
+        }
+    }
+
+    /// Sends a whole frame over a frame or signal mode link.
+    ///
+    /// Returns the simulation time at which the frame arrives. Frames are counted by the link,
+    /// the NIC's frame counters are left to the MAC.
+    pub async fn transmit_frame(&self, frame: Vec<u8>) -> Result<Duration, PhysicalError> {
+        let mut handle = self.connection.lock().unwrap();
+        let conn = handle.as_ref().ok_or(PhysicalError::LinkDown)?;
+        if conn.properties().mode == LinkMode::Byte {
+            tracing::warn!(mac = %self.mac, "frame sent on a byte mode link");
+            return Err(PhysicalError::ByteMode);
+        }
+        let octets = frame.len();
+        match conn.send_frame(frame) {
+            Ok(arrival) => Ok(arrival),
+            Err(TrySendError::Closed(_)) => {
+                tracing::debug!(mac = %self.mac, "link down");
+                *handle = None;
+                Err(PhysicalError::LinkDown)
+            }
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!(mac = %self.mac, octets, "link buffer overflow");
+                self.statistics.buffer_overflows.add(octets as u64);
+                Err(PhysicalError::BufferOverflow(octets))
+            }
+        }
+    }
+
+    /// Repeats a frame out of a device's port, a frame that cannot be sent is counted as dropped
+    pub async fn relay_frame(&self, frame: Vec<u8>) {
+        let octets = frame.len();
+        if let Err(error) = self.transmit_frame(frame).await {
+            tracing::warn!(mac = %self.mac, octets, %error, "frame dropped");
+            self.statistics.frames_dropped.increment();
+        }
+    }
+
+    /// Receives the next frame that has arrived over a frame or signal mode link
+    pub async fn recieve_frame(&self) -> Option<Vec<u8>> {
+        let mut handle = self.connection.lock().unwrap();
+        match handle.as_mut()?.recv_frame() {
+            Ok(frame) => Some(frame),
+            Err(TryRecvError::Disconnected) => {
+                tracing::debug!(mac = %self.mac, "link down");
+                *handle = None;
+                None
+            }
+            Err(TryRecvError::Empty) => None,
         }
-        None
     }
 }
 
@@ -107,7 +251,7 @@ impl NIC {
 mod tests {
     use super::*;
 
-    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    #[tokio::test]
     async fn test_nic_set_transmit() {
         let nic = NIC::default();
         assert!(!nic.transmitting());
@@ -115,7 +259,7 @@ mod tests {
         assert!(nic.transmitting());
     }
 
-    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    #[tokio::test]
     async fn test_auto_disconnect() {
         let nic1 = NIC::default();
         let nic2 = NIC::default();
@@ -132,7 +276,7 @@ mod tests {
         assert!(!nic2.is_connected());
     }
 
-    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    #[tokio::test]
     async fn test_transmit_recieve() {
         let nic1 = NIC::default();
         let nic2 = NIC::default();
@@ -143,5 +287,7 @@ mod tests {
 
         nic1.transmit(0x42).await;
         assert_eq!(nic2.recieve().await, Some(0x42));
+        assert_eq!(nic1.link_statistics().unwrap().octets_out, 1);
+        assert_eq!(nic2.link_statistics().unwrap().octets_in, 1);
     }
 }
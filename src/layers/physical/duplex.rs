@@ -0,0 +1,59 @@
+/*
+  Reference:
+    IEEE 802.3, Clause 28 (Auto-Negotiation) and Clause 4.2.3.2.4 (Late collisions)
+*/
+
+/// Operating mode of a link after negotiation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Duplex {
+    /// The medium is shared, stations defer to a carrier and detect collisions
+    #[default]
+    Half,
+    /// Both ends may send at the same time, carrier sense and collision detection are not used
+    Full,
+}
+
+/// Duplex configuration of a NIC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplexSetting {
+    /// Advertise both modes and agree on the best one with the link partner
+    #[default]
+    Auto,
+    /// Forced mode, no auto-negotiation takes place
+    Forced(Duplex),
+}
+
+impl DuplexSetting {
+    /// Resolves the mode this end operates in when connected to a partner with the given setting.
+    ///
+    /// Two auto-negotiating ends agree on full duplex. An auto-negotiating end facing a forced
+    /// partner receives no advertisement and falls back to half duplex through parallel
+    /// detection, so forcing only one end to full duplex results in a duplex mismatch.
+    pub fn resolve(&self, partner: &DuplexSetting) -> Duplex {
+        match (self, partner) {
+            (DuplexSetting::Forced(duplex), _) => *duplex,
+            (DuplexSetting::Auto, DuplexSetting::Auto) => Duplex::Full,
+            (DuplexSetting::Auto, DuplexSetting::Forced(_)) => Duplex::Half,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let auto = DuplexSetting::Auto;
+        let full = DuplexSetting::Forced(Duplex::Full);
+        let half = DuplexSetting::Forced(Duplex::Half);
+
+        assert_eq!(auto.resolve(&auto), Duplex::Full);
+        assert_eq!(half.resolve(&auto), Duplex::Half);
+        assert_eq!(auto.resolve(&half), Duplex::Half);
+
+        // Duplex mismatch
+        assert_eq!(full.resolve(&auto), Duplex::Full);
+        assert_eq!(auto.resolve(&full), Duplex::Half);
+    }
+}
@@ -0,0 +1,297 @@
+/*
+  Reference:
+    IEEE 802.3, Clause 4.2.5 (Preamble and SFD)
+    ISO/IEC 13239 (HDLC), Clause 4.3 (Transparency)
+    RFC 1662 (PPP in HDLC-like Framing), Section 4.2 (Octet-stuffed framing)
+*/
+use super::line_coding::{PREAMBLE, SFD};
+
+/// Opening and closing flag of HDLC-like framing
+const HDLC_FLAG: u8 = 0x7E;
+
+/// Control escape of octet-stuffed framing
+const ESCAPE: u8 = 0x7D;
+
+/// Escaped octets are sent XORed with this value
+const ESCAPE_XOR: u8 = 0x20;
+
+/// Size of the length field of length-prefixed framing
+const LENGTH_SIZE: usize = 2;
+
+/// Ways the receiver can fail to delimit a frame, independent of its frame check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramingError {
+    /// Bytes were received but no start of a frame was found in them
+    NoDelimiter,
+    /// The sender aborted the frame, or the bits received look like it did
+    Aborted,
+    /// The frame was not closed before the end of the transmission
+    Unterminated,
+    /// After removing the stuffed bits the frame is not a whole number of octets
+    NotOctetAligned,
+    /// A length-prefixed frame is longer than the bytes that followed it
+    Truncated,
+}
+
+/// How the start and end of frames are marked on a link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+// Picked per link, the demo only runs Ethernet links
+#[allow(dead_code)]
+pub enum Framing {
+    /// Preamble and start frame delimiter in front of every frame, the end is the loss of carrier
+    #[default]
+    Preamble,
+    /// HDLC flags around every frame, with a zero bit stuffed after five consecutive ones
+    BitStuffing,
+    /// PPP flags around every frame, with flag and escape octets escaped
+    ByteStuffing,
+    /// The length of every frame in front of it
+    LengthPrefix,
+}
+
+fn to_bits(bytes: &[u8]) -> Vec<bool> {
+    bytes.iter().flat_map(|byte| (0..8).map(move |i| byte >> i & 1 == 1)).collect()
+}
+
+fn to_bytes(bits: &[bool]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|bits| bits.iter().rev().fold(0, |byte, &bit| byte << 1 | bit as u8))
+        .collect()
+}
+
+fn stuff_bits(frame: &[u8]) -> Vec<u8> {
+    let flag = to_bits(&[HDLC_FLAG]);
+    let mut bits = flag.clone();
+    let mut ones = 0;
+    for bit in to_bits(frame) {
+        bits.push(bit);
+        ones = if bit { ones + 1 } else { 0 };
+        if ones == 5 {
+            bits.push(false);
+            ones = 0;
+        }
+    }
+    bits.extend(flag);
+    // The last octet is filled up with zeros, which the receiver ignores outside a frame
+    to_bytes(&bits)
+}
+
+fn unstuff_bits(bytes: &[u8]) -> Vec<Result<Vec<u8>, FramingError>> {
+    let mut frames = Vec::new();
+    let mut current = Vec::new();
+    let mut in_frame = false;
+    let mut ones = 0;
+    for bit in to_bits(bytes) {
+        if bit {
+            ones += 1;
+            if ones == 7 && in_frame {
+                frames.push(Err(FramingError::Aborted));
+                current.clear();
+                in_frame = false;
+            }
+            if in_frame {
+                current.push(bit);
+            }
+            continue;
+        }
+
+        match ones {
+            6 => {
+                // A flag, its leading zero and ones have already been taken for data
+                current.truncate(current.len().saturating_sub(7));
+                let fill = current.len() < 8 && !current.contains(&true);
+                if in_frame && !fill {
+                    frames.push(match current.len() % 8 {
+                        0 => Ok(to_bytes(&current)),
+                        _ => Err(FramingError::NotOctetAligned),
+                    });
+                }
+                current.clear();
+                in_frame = true;
+            }
+            5 => (),
+            _ if in_frame => current.push(bit),
+            _ => (),
+        }
+        ones = 0;
+    }
+
+    if in_frame && current.iter().any(|&bit| bit) {
+        frames.push(Err(FramingError::Unterminated));
+    }
+    if !in_frame && frames.is_empty() && !bytes.is_empty() {
+        frames.push(Err(FramingError::NoDelimiter));
+    }
+    frames
+}
+
+fn stuff_bytes(frame: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![HDLC_FLAG];
+    for &byte in frame {
+        match byte {
+            HDLC_FLAG | ESCAPE => bytes.extend([ESCAPE, byte ^ ESCAPE_XOR]),
+            _ => bytes.push(byte),
+        }
+    }
+    bytes.push(HDLC_FLAG);
+    bytes
+}
+
+fn unstuff_bytes(bytes: &[u8]) -> Vec<Result<Vec<u8>, FramingError>> {
+    let Some(start) = bytes.iter().position(|&byte| byte == HDLC_FLAG) else {
+        return match bytes.is_empty() {
+            true => Vec::new(),
+            false => vec![Err(FramingError::NoDelimiter)],
+        };
+    };
+
+    let mut frames = Vec::new();
+    let mut current = Vec::new();
+    let mut escaped = false;
+    for &byte in &bytes[start + 1..] {
+        match byte {
+            HDLC_FLAG if escaped => {
+                frames.push(Err(FramingError::Aborted));
+                current.clear();
+                escaped = false;
+            }
+            HDLC_FLAG => {
+                if !current.is_empty() {
+                    frames.push(Ok(std::mem::take(&mut current)));
+                }
+            }
+            ESCAPE => escaped = true,
+            _ if escaped => {
+                current.push(byte ^ ESCAPE_XOR);
+                escaped = false;
+            }
+            _ => current.push(byte),
+        }
+    }
+    if !current.is_empty() || escaped {
+        frames.push(Err(FramingError::Unterminated));
+    }
+    frames
+}
+
+/// Splits at every complete preamble and SFD.
+///
+/// The first frame starts after the first SFD that follows a preamble octet, so a receiver that
+/// missed part of the preamble or picked up noise before it still synchronises. Payloads that
+/// happen to contain a whole preamble and SFD are split as well and fail their frame check.
+fn split_preamble(bytes: &[u8]) -> Vec<Result<Vec<u8>, FramingError>> {
+    let delimiter = [PREAMBLE.as_ref(), &[SFD]].concat();
+    let Some(sfd) = bytes.windows(2).position(|pair| pair == [PREAMBLE[0], SFD]) else {
+        return match bytes.is_empty() {
+            true => Vec::new(),
+            false => vec![Err(FramingError::NoDelimiter)],
+        };
+    };
+
+    let mut frames = Vec::new();
+    let mut start = sfd + 2;
+    let mut i = start;
+    while i + delimiter.len() <= bytes.len() {
+        if bytes[i..i + delimiter.len()] == delimiter[..] {
+            frames.push(Ok(bytes[start..i].to_vec()));
+            start = i + delimiter.len();
+            i = start;
+        } else {
+            i += 1;
+        }
+    }
+    frames.push(Ok(bytes[start..].to_vec()));
+    frames
+}
+
+fn split_length_prefixed(mut bytes: &[u8]) -> Vec<Result<Vec<u8>, FramingError>> {
+    let mut frames = Vec::new();
+    while !bytes.is_empty() {
+        if bytes.len() < LENGTH_SIZE {
+            frames.push(Err(FramingError::Truncated));
+            break;
+        }
+        let length = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+        let Some(frame) = bytes.get(LENGTH_SIZE..LENGTH_SIZE + length) else {
+            frames.push(Err(FramingError::Truncated));
+            break;
+        };
+        frames.push(Ok(frame.to_vec()));
+        bytes = &bytes[LENGTH_SIZE + length..];
+    }
+    frames
+}
+
+impl Framing {
+    /// Delimits a frame for transmission
+    pub fn frame(&self, frame: &[u8]) -> Vec<u8> {
+        match self {
+            Framing::Preamble => [PREAMBLE.as_ref(), &[SFD], frame].concat(),
+            Framing::BitStuffing => stuff_bits(frame),
+            Framing::ByteStuffing => stuff_bytes(frame),
+            Framing::LengthPrefix => [(frame.len() as u16).to_be_bytes().as_ref(), frame].concat(),
+        }
+    }
+
+    /// Recovers the frames from the bytes received while a carrier was sensed
+    pub fn deframe(&self, bytes: &[u8]) -> Vec<Result<Vec<u8>, FramingError>> {
+        match self {
+            Framing::Preamble => split_preamble(bytes),
+            Framing::BitStuffing => unstuff_bits(bytes),
+            Framing::ByteStuffing => unstuff_bytes(bytes),
+            Framing::LengthPrefix => split_length_prefixed(bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAMINGS: [Framing; 4] = [
+        Framing::Preamble,
+        Framing::BitStuffing,
+        Framing::ByteStuffing,
+        Framing::LengthPrefix,
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        let first: Vec<u8> = (0..=255).collect();
+        let second = vec![HDLC_FLAG, ESCAPE, 0xFF, 0xFF, 0x3E];
+        for framing in FRAMINGS {
+            let bytes = [framing.frame(&first), framing.frame(&second)].concat();
+            assert_eq!(framing.deframe(&bytes), vec![Ok(first.clone()), Ok(second.clone())], "{:?}", framing);
+            assert_eq!(framing.deframe(&[]), vec![], "{:?}", framing);
+        }
+    }
+
+    #[test]
+    fn test_stuffing() {
+        assert_eq!(Framing::ByteStuffing.frame(&[0x7E, 0x01]), [0x7E, 0x7D, 0x5E, 0x01, 0x7E]);
+        // Eight ones need one stuffed zero, so the frame no longer ends on an octet boundary
+        assert_eq!(Framing::BitStuffing.frame(&[0xFF]).len(), 4);
+    }
+
+    #[test]
+    fn test_resynchronise() {
+        let noise = [0x12, 0x34, 0x55, 0x00];
+        let frame = [0xAA, 0xBB];
+        for framing in [Framing::Preamble, Framing::BitStuffing, Framing::ByteStuffing] {
+            let bytes = [noise.as_ref(), &framing.frame(&frame)].concat();
+            assert_eq!(framing.deframe(&bytes), vec![Ok(frame.to_vec())], "{:?}", framing);
+        }
+
+        // A receiver that lost most of the preamble still finds the SFD
+        assert_eq!(Framing::Preamble.deframe(&[0x55, SFD, 0xAA]), vec![Ok(vec![0xAA])]);
+    }
+
+    #[test]
+    fn test_framing_errors() {
+        assert_eq!(Framing::Preamble.deframe(&[0x12, 0x34]), vec![Err(FramingError::NoDelimiter)]);
+        assert_eq!(Framing::ByteStuffing.deframe(&[0x7E, 0x01, 0x7D, 0x7E]), vec![Err(FramingError::Aborted)]);
+        assert_eq!(Framing::ByteStuffing.deframe(&[0x7E, 0x01]), vec![Err(FramingError::Unterminated)]);
+        assert_eq!(Framing::LengthPrefix.deframe(&[0x00, 0x05, 0x01]), vec![Err(FramingError::Truncated)]);
+        assert_eq!(Framing::BitStuffing.deframe(&[0x7E, 0xFF, 0xFF]), vec![Err(FramingError::Aborted)]);
+    }
+}
@@ -0,0 +1,383 @@
+/*
+  Reference:
+    IEEE 802.3, Clause 4.2.5 (Preamble and SFD), Clause 7.3 (Manchester encoding)
+    IEEE 802.3, Clause 24 (4B/5B), Clause 25 (MLT-3), Clause 36 (8B/10B)
+    IEEE 802.5 (Differential Manchester)
+*/
+
+/// Signal level of a symbol on the line
+pub type Level = i8;
+
+/// Preamble bytes that let the receiver lock onto the sender's clock
+pub const PREAMBLE: [u8; 7] = [0x55; 7];
+
+/// Start frame delimiter, marks the end of the preamble
+pub const SFD: u8 = 0xD5;
+
+/// The SFD as it is sent, least significant bit first
+const SFD_BITS: [bool; 8] = [true, false, true, false, true, false, true, true];
+
+/// Line level before the first symbol
+const IDLE: Level = -1;
+
+/// 4B/5B code groups of the data nibbles, sent most significant bit first
+const FOUR_FIVE: [u8; 16] = [
+    0b11110, 0b01001, 0b10100, 0b10101, 0b01010, 0b01011, 0b01110, 0b01111,
+    0b10010, 0b10011, 0b10110, 0b10111, 0b11010, 0b11011, 0b11100, 0b11101,
+];
+
+/// 8B/10B 5b/6b sub-blocks `abcdei` for negative running disparity
+const FIVE_SIX: [u8; 32] = [
+    0b100111, 0b011101, 0b101101, 0b110001, 0b110101, 0b101001, 0b011001, 0b111000,
+    0b111001, 0b100101, 0b010101, 0b110100, 0b001101, 0b101100, 0b011100, 0b010111,
+    0b011011, 0b100011, 0b010011, 0b110010, 0b001011, 0b101010, 0b011010, 0b111010,
+    0b110011, 0b100110, 0b010110, 0b110110, 0b001110, 0b101110, 0b011110, 0b101011,
+];
+
+/// 8B/10B 3b/4b sub-blocks `fghj` for negative running disparity
+const THREE_FOUR: [u8; 8] = [0b1011, 0b1001, 0b0101, 0b1100, 0b1101, 0b1010, 0b0110, 0b1110];
+
+/// Alternate D.x.7 sub-block that avoids runs of five equal bits
+const THREE_FOUR_A7: u8 = 0b0111;
+
+/// Errors a receiver can run into when recovering a frame from the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCodeError {
+    /// The symbol at the given position is not valid for the line code
+    InvalidSymbol(usize),
+    /// No start frame delimiter was found in the signal
+    NoStartFrameDelimiter,
+}
+
+/// Line codes that map bits onto signal levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+// Picked per link, the demo only runs byte mode links
+#[allow(dead_code)]
+pub enum LineCode {
+    /// A level per bit, high for one and low for zero
+    #[default]
+    Nrz,
+    /// A transition for every one bit
+    Nrzi,
+    /// Two symbols per bit with a transition in the middle, low to high for one
+    Manchester,
+    /// Two symbols per bit with a transition in the middle and one at the start for zero
+    DifferentialManchester,
+    /// Every nibble is sent as a five bit code group with NRZI
+    FourBFiveB,
+    /// Every byte is sent as a DC balanced ten bit code group with NRZ
+    EightBTenB,
+    /// A one bit steps through the levels 0, +1, 0, -1
+    Mlt3,
+}
+
+fn to_bits(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
+    bytes.iter().flat_map(|byte| (0..8).map(move |i| byte >> i & 1 == 1))
+}
+
+fn to_bytes(bits: &[bool]) -> Vec<u8> {
+    bits.chunks_exact(8)
+        .map(|bits| bits.iter().rev().fold(0, |byte, &bit| byte << 1 | bit as u8))
+        .collect()
+}
+
+/// Bits of a code group, most significant first
+fn group_bits(group: u8, width: usize) -> impl Iterator<Item = bool> {
+    (0..width).rev().map(move |i| group >> i & 1 == 1)
+}
+
+fn group_value(bits: &[bool]) -> u8 {
+    bits.iter().fold(0, |group, &bit| group << 1 | bit as u8)
+}
+
+fn nrzi(bits: impl Iterator<Item = bool>) -> Vec<Level> {
+    let mut level = IDLE;
+    bits.map(|bit| {
+        if bit {
+            level = -level;
+        }
+        level
+    })
+    .collect()
+}
+
+fn from_nrzi(signal: &[Level]) -> Vec<bool> {
+    let mut previous = IDLE;
+    signal
+        .iter()
+        .map(|&level| std::mem::replace(&mut previous, level) != level)
+        .collect()
+}
+
+/// The 8B/10B sub-block for the running disparity, given its code for negative disparity.
+///
+/// Unbalanced sub-blocks and the balanced D.7 and D.x.3 ones alternate with the disparity.
+fn sub_block(code: u8, width: u32, alternates: bool, positive: bool) -> u8 {
+    if positive && (code.count_ones() * 2 != width || alternates) {
+        !code & ((1 << width) - 1)
+    } else {
+        code
+    }
+}
+
+fn running_disparity(code: u8, width: u32, positive: bool) -> bool {
+    match (code.count_ones() * 2).cmp(&width) {
+        std::cmp::Ordering::Equal => positive,
+        ordering => ordering.is_gt(),
+    }
+}
+
+fn encode_8b10b(bytes: &[u8]) -> Vec<bool> {
+    let mut positive = false;
+    let mut bits = Vec::with_capacity(bytes.len() * 10);
+    for byte in bytes {
+        let (x, y) = ((byte & 0x1F) as usize, (byte >> 5) as usize);
+        let six = sub_block(FIVE_SIX[x], 6, x == 7, positive);
+        positive = running_disparity(six, 6, positive);
+
+        let alternate = y == 7
+            && ((!positive && matches!(x, 17 | 18 | 20)) || (positive && matches!(x, 11 | 13 | 14)));
+        let four = match alternate {
+            true => sub_block(THREE_FOUR_A7, 4, false, positive),
+            false => sub_block(THREE_FOUR[y], 4, y == 3, positive),
+        };
+        positive = running_disparity(four, 4, positive);
+
+        bits.extend(group_bits(six, 6).chain(group_bits(four, 4)));
+    }
+    bits
+}
+
+fn decode_8b10b(bits: &[bool]) -> Result<Vec<bool>, LineCodeError> {
+    let matches = |code: u8, width: u32, alternates: bool, group: u8| {
+        sub_block(code, width, alternates, false) == group || sub_block(code, width, alternates, true) == group
+    };
+
+    let mut bytes = Vec::with_capacity(bits.len() / 10);
+    for (i, group) in bits.chunks_exact(10).enumerate() {
+        let (six, four) = (group_value(&group[..6]), group_value(&group[6..]));
+        let x = (0..32).find(|&x| matches(FIVE_SIX[x], 6, x == 7, six));
+        let y = (0..8)
+            .find(|&y| matches(THREE_FOUR[y], 4, y == 3, four))
+            .or_else(|| matches(THREE_FOUR_A7, 4, false, four).then_some(7));
+        match (x, y) {
+            (Some(x), Some(y)) => bytes.push((y << 5 | x) as u8),
+            _ => return Err(LineCodeError::InvalidSymbol(i * 10)),
+        }
+    }
+    Ok(to_bits(&bytes).collect())
+}
+
+impl LineCode {
+    /// Encodes bytes into symbols, bits are sent least significant first
+    pub fn encode(&self, bytes: &[u8]) -> Vec<Level> {
+        let level = |bit: bool| if bit { 1 } else { -1 };
+        match self {
+            LineCode::Nrz => to_bits(bytes).map(level).collect(),
+            LineCode::Nrzi => nrzi(to_bits(bytes)),
+            LineCode::Manchester => to_bits(bytes).flat_map(|bit| [-level(bit), level(bit)]).collect(),
+            LineCode::DifferentialManchester => {
+                let mut previous = IDLE;
+                to_bits(bytes)
+                    .flat_map(|bit| {
+                        let first = if bit { previous } else { -previous };
+                        previous = -first;
+                        [first, -first]
+                    })
+                    .collect()
+            }
+            LineCode::FourBFiveB => nrzi(
+                bytes
+                    .iter()
+                    .flat_map(|byte| [byte & 0x0F, byte >> 4])
+                    .flat_map(|nibble| group_bits(FOUR_FIVE[nibble as usize], 5)),
+            ),
+            LineCode::EightBTenB => encode_8b10b(bytes).into_iter().map(level).collect(),
+            LineCode::Mlt3 => {
+                const CYCLE: [Level; 4] = [0, 1, 0, -1];
+                let mut state = 0;
+                to_bits(bytes)
+                    .map(|bit| {
+                        state = (state + bit as usize) % CYCLE.len();
+                        CYCLE[state]
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Decodes symbols into the bits they carry.
+    ///
+    /// Block codes are decoded in code groups from the first symbol, trailing symbols that do
+    /// not make up a whole bit or code group are ignored.
+    pub fn decode(&self, signal: &[Level]) -> Result<Vec<bool>, LineCodeError> {
+        match self {
+            LineCode::Nrz => Ok(signal.iter().map(|&level| level > 0).collect()),
+            LineCode::Nrzi => Ok(from_nrzi(signal)),
+            LineCode::Manchester => signal
+                .chunks_exact(2)
+                .enumerate()
+                .map(|(i, halves)| match halves {
+                    [first, second] if first != second => Ok(second > first),
+                    _ => Err(LineCodeError::InvalidSymbol(i * 2)),
+                })
+                .collect(),
+            LineCode::DifferentialManchester => {
+                let mut previous = IDLE;
+                signal
+                    .chunks_exact(2)
+                    .enumerate()
+                    .map(|(i, halves)| match halves {
+                        [first, second] if first != second => {
+                            let bit = *first == previous;
+                            previous = *second;
+                            Ok(bit)
+                        }
+                        _ => Err(LineCodeError::InvalidSymbol(i * 2)),
+                    })
+                    .collect()
+            }
+            LineCode::FourBFiveB => {
+                let bits = from_nrzi(signal);
+                let mut nibbles = Vec::with_capacity(bits.len() / 5);
+                for (i, group) in bits.chunks_exact(5).enumerate() {
+                    match FOUR_FIVE.iter().position(|&code| code == group_value(group)) {
+                        Some(nibble) => nibbles.push(nibble as u8),
+                        None => return Err(LineCodeError::InvalidSymbol(i * 5)),
+                    }
+                }
+                Ok(nibbles.iter().flat_map(|nibble| (0..4).map(move |i| nibble >> i & 1 == 1)).collect())
+            }
+            LineCode::EightBTenB => decode_8b10b(&signal.iter().map(|&level| level > 0).collect::<Vec<_>>()),
+            LineCode::Mlt3 => {
+                let mut previous = 0;
+                Ok(signal
+                    .iter()
+                    .map(|&level| std::mem::replace(&mut previous, level) != level)
+                    .collect())
+            }
+        }
+    }
+}
+
+/// Samples a signal with a receiver clock that runs `drift` parts per million fast or slow.
+///
+/// Every transition realigns the receiver clock. Between transitions the phase error
+/// accumulates, once it reaches a whole symbol the receiver samples a symbol twice or misses one.
+pub fn sample(signal: &[Level], drift: i32) -> Vec<Level> {
+    let drift = drift as f64 / 1_000_000.0;
+    let mut phase = 0.0;
+    let mut previous = None;
+    let mut samples = Vec::with_capacity(signal.len());
+    for &level in signal {
+        if previous.is_some_and(|previous| previous != level) {
+            phase = 0.0;
+        }
+        previous = Some(level);
+
+        phase += drift;
+        if phase <= -1.0 {
+            phase += 1.0;
+            continue;
+        }
+        samples.push(level);
+        if phase >= 1.0 {
+            phase -= 1.0;
+            samples.push(level);
+        }
+    }
+    samples
+}
+
+/// The line code of a link and the clock offset of its receivers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LineCoding {
+    pub code: LineCode,
+    /// Offset of the receiver clock from the sender clock in parts per million
+    pub clock_drift: i32,
+}
+
+impl LineCoding {
+    /// Line codes a frame with the preamble and start frame delimiter in front of it
+    pub fn transmit(&self, frame: &[u8]) -> Vec<Level> {
+        self.code.encode(&[PREAMBLE.as_ref(), &[SFD], frame].concat())
+    }
+
+    /// Recovers a frame from the signal as sampled by the receiver.
+    ///
+    /// The receiver synchronises on the first start frame delimiter, so bits lost or garbled
+    /// while locking onto the preamble do not affect the frame.
+    pub fn receive(&self, signal: &[Level]) -> Result<Vec<u8>, LineCodeError> {
+        let bits = self.code.decode(&sample(signal, self.clock_drift))?;
+        let start = bits
+            .windows(SFD_BITS.len())
+            .position(|window| window == SFD_BITS)
+            .ok_or(LineCodeError::NoStartFrameDelimiter)?;
+        Ok(to_bytes(&bits[start + SFD_BITS.len()..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODES: [LineCode; 7] = [
+        LineCode::Nrz,
+        LineCode::Nrzi,
+        LineCode::Manchester,
+        LineCode::DifferentialManchester,
+        LineCode::FourBFiveB,
+        LineCode::EightBTenB,
+        LineCode::Mlt3,
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for code in CODES {
+            let signal = code.encode(&bytes);
+            assert_eq!(code.decode(&signal), Ok(to_bits(&bytes).collect()), "{:?}", code);
+
+            let coding = LineCoding { code, clock_drift: 0 };
+            assert_eq!(coding.receive(&coding.transmit(&bytes)), Ok(bytes.clone()), "{:?}", code);
+        }
+    }
+
+    #[test]
+    fn test_encodings() {
+        // 0x0D is sent as 1, 0, 1, 1, 0, 0, 0, 0
+        assert_eq!(LineCode::Nrz.encode(&[0x0D]), [1, -1, 1, 1, -1, -1, -1, -1]);
+        assert_eq!(LineCode::Nrzi.encode(&[0x0D]), [1, 1, -1, 1, 1, 1, 1, 1]);
+        assert_eq!(LineCode::Mlt3.encode(&[0x0D]), [1, 1, 0, -1, -1, -1, -1, -1]);
+        assert_eq!(LineCode::Manchester.encode(&[0x0D])[..4], [-1, 1, 1, -1]);
+        assert_eq!(LineCode::DifferentialManchester.encode(&[0x0D])[..4], [-1, 1, -1, 1]);
+    }
+
+    #[test]
+    fn test_8b10b_disparity() {
+        let signal = LineCode::EightBTenB.encode(&[0x00; 100]);
+        let disparity: i32 = signal.iter().map(|&level| level as i32).sum();
+        assert!(disparity.abs() <= 2);
+        assert_eq!(LineCode::EightBTenB.decode(&[1; 10]), Err(LineCodeError::InvalidSymbol(0)));
+    }
+
+    #[test]
+    fn test_clock_recovery() {
+        let frame = [[0x00; 31].as_ref(), &[0xFF]].concat();
+        let nrz = LineCoding { code: LineCode::Nrz, clock_drift: 10_000 };
+        let manchester = LineCoding { code: LineCode::Manchester, clock_drift: 10_000 };
+
+        // Without transitions a receiver clock 1% off slips a bit every 100 bits
+        assert_ne!(nrz.receive(&nrz.transmit(&frame)), Ok(frame.clone()));
+        assert_eq!(manchester.receive(&manchester.transmit(&frame)), Ok(frame.clone()));
+    }
+
+    #[test]
+    fn test_resynchronise_on_sfd() {
+        let coding = LineCoding::default();
+        let mut signal = coding.transmit(&[0x42]);
+        signal[..12].fill(1);
+        assert_eq!(coding.receive(&signal), Ok(vec![0x42]));
+        assert_eq!(coding.receive(&signal[60..]), Err(LineCodeError::NoStartFrameDelimiter));
+    }
+}
@@ -1,49 +1,305 @@
+use super::framing::Framing;
+use super::line_coding::{LineCoding, PREAMBLE};
+use crate::layers::statistics::{LinkCounters, LinkStatistics};
+use crate::utils::clock;
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc,
+};
 use tokio::sync::mpsc::{
     channel,
     error::{TryRecvError, TrySendError},
     Receiver, Sender,
 };
+use tokio::time::Duration;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Byte times a carrier stays up after the last byte, so it does not drop between the bytes
+/// a device relays on its own ticks
+const CARRIER_HOLD: usize = 3;
+
+/// What a byte mode link carries in one byte time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symbol {
+    Data(u8),
+    /// Carrier extension, which holds the carrier up without carrying data
+    Extension,
+}
+
+/// Granularity in which a connection carries data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+// Picked per link, the demo only runs byte mode links
+#[allow(dead_code)]
+pub enum LinkMode {
+    /// Bytes are relayed one per tick, as needed to study the MAC
+    #[default]
+    Byte,
+    /// Whole frames travel as single events that arrive after their transmission time
+    Frame,
+    /// Like `Frame`, but every frame is line coded behind a preamble and recovered by the
+    /// receiver, so frames can be lost to coding and synchronisation errors
+    Signal(LineCoding),
+}
+
+/// Nominal properties of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkProperties {
+    /// Bits per second of simulation time
+    pub bandwidth: u64,
+    /// Propagation delay
+    pub delay: Duration,
+    pub mode: LinkMode,
+    /// How the MAC delimits frames sent over the link
+    pub framing: Framing,
+}
+
+impl Default for LinkProperties {
+    /// A byte per byte time without propagation delay, which is how bytes are relayed by devices
+    fn default() -> Self {
+        LinkProperties {
+            bandwidth: 8 * Duration::from_secs(1).as_nanos() as u64 / clock::BYTE_TIME.as_nanos() as u64,
+            delay: Duration::ZERO,
+            mode: LinkMode::Byte,
+            framing: Framing::Preamble,
+        }
+    }
+}
+
+impl LinkProperties {
+    /// Properties of a frame mode link with the given bandwidth and delay
+    pub fn frames(bandwidth: u64, delay: Duration) -> Self {
+        LinkProperties {
+            bandwidth,
+            delay,
+            mode: LinkMode::Frame,
+            framing: Framing::Preamble,
+        }
+    }
+
+    /// Time it takes to put `octets` on the link, without bandwidth the octets never leave
+    pub fn transmission_time(&self, octets: usize) -> Duration {
+        let bits = (octets as u64 * 8).saturating_mul(1_000_000_000);
+        Duration::from_nanos(bits.checked_div(self.bandwidth).unwrap_or(u64::MAX))
+    }
+}
+
+/// A frame on its way through a frame mode link.
+struct Transfer {
+    frame: Vec<u8>,
+    /// Simulation time at which the last bit reaches the other end
+    arrival: Duration,
+}
 
 /// A `Physical Layer` primitive that represents a one way link between two endpoints.
 ///
 /// A connection is established by creating a pair of links with interchanged senders and receivers.
+/// Both links of a connection share its `id` and properties.
+/// Bytes and frames travel on separate channels, which one is used depends on the `LinkMode`.
 pub struct Link {
-    tx: Sender<u8>,
-    rx: Receiver<u8>,
+    id: usize,
+    properties: LinkProperties,
+    tx: Sender<Symbol>,
+    rx: Receiver<Symbol>,
+    frame_tx: Sender<Transfer>,
+    frame_rx: Receiver<Transfer>,
+    /// A received frame that has not arrived yet
+    pending: Option<Transfer>,
+    /// Simulation time in nanoseconds at which the last frame sent has left the link
+    busy_until: AtomicU64,
+    /// Simulation time in nanoseconds until which the carrier of the bytes sent is sensed
+    carrier_out: Arc<AtomicU64>,
+    /// The same for the bytes sent from the other end
+    carrier_in: Arc<AtomicU64>,
+    statistics: LinkStatistics,
 }
 
 impl Link {
-    fn oneway(tx: Sender<u8>, rx: Receiver<u8>) -> Self {
-        Self { tx, rx }
+    fn oneway(
+        id: usize,
+        properties: LinkProperties,
+        (tx, rx): (Sender<Symbol>, Receiver<Symbol>),
+        (frame_tx, frame_rx): (Sender<Transfer>, Receiver<Transfer>),
+        (carrier_out, carrier_in): (Arc<AtomicU64>, Arc<AtomicU64>),
+    ) -> Self {
+        Self {
+            id,
+            properties,
+            tx,
+            rx,
+            frame_tx,
+            frame_rx,
+            pending: None,
+            busy_until: AtomicU64::new(0),
+            carrier_out,
+            carrier_in,
+            statistics: Default::default(),
+        }
     }
 
     /// Create a new connection and return it as a pair of one way links.
     pub fn connection() -> (Self, Self) {
+        Self::connection_with(Default::default())
+    }
+
+    /// Create a new connection with the given properties.
+    pub fn connection_with(properties: LinkProperties) -> (Self, Self) {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
         let (tx1, rx1) = channel(2000);
         let (tx2, rx2) = channel(2000);
-        (Self::oneway(tx1, rx2), Self::oneway(tx2, rx1))
+        let (frame_tx1, frame_rx1) = channel(64);
+        let (frame_tx2, frame_rx2) = channel(64);
+        let carrier1 = Arc::new(AtomicU64::new(0));
+        let carrier2 = Arc::new(AtomicU64::new(0));
+        (
+            Self::oneway(
+                id,
+                properties,
+                (tx1, rx2),
+                (frame_tx1, frame_rx2),
+                (carrier1.clone(), carrier2.clone()),
+            ),
+            Self::oneway(id, properties, (tx2, rx1), (frame_tx2, frame_rx1), (carrier2, carrier1)),
+        )
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn properties(&self) -> LinkProperties {
+        self.properties
+    }
+
+    /// Whether the other end of the connection still exists
+    pub fn is_up(&self) -> bool {
+        !self.tx.is_closed()
     }
 
     /// Send a byte of data through the link.
     ///
     /// The reciever of the data needs to call `recv` on it's end of the link.
     pub fn send(&self, data: u8) -> Result<(), TrySendError<u8>> {
-        self.tx.try_send(data)
+        self.send_symbol(Symbol::Data(data)).map_err(|e| match e {
+            TrySendError::Full(_) => TrySendError::Full(data),
+            TrySendError::Closed(_) => TrySendError::Closed(data),
+        })
+    }
+
+    /// Send a symbol through the link, only data symbols count as octets.
+    pub fn send_symbol(&self, symbol: Symbol) -> Result<(), TrySendError<Symbol>> {
+        let status = self.tx.try_send(symbol);
+        match status {
+            Ok(()) => {
+                let carrier = clock::now() + clock::byte_times(CARRIER_HOLD);
+                self.carrier_out.store(carrier.as_nanos() as u64, Ordering::Relaxed);
+                if let Symbol::Data(_) = symbol {
+                    self.statistics.octets_out.increment();
+                }
+            }
+            Err(TrySendError::Full(_)) => self.statistics.overflows.increment(),
+            Err(TrySendError::Closed(_)) => (),
+        }
+        status
     }
 
-    /// Receive a byte of data from the link.
+    /// Receive a byte of data from the link, skipping carrier extension.
     pub fn recv(&mut self) -> Result<u8, TryRecvError> {
-        self.rx.try_recv()
+        loop {
+            if let Symbol::Data(byte) = self.recv_symbol()? {
+                return Ok(byte);
+            }
+        }
     }
 
+    /// Receive the next symbol from the link.
+    pub fn recv_symbol(&mut self) -> Result<Symbol, TryRecvError> {
+        let symbol = self.rx.try_recv();
+        if let Ok(Symbol::Data(_)) = symbol {
+            self.statistics.octets_in.increment();
+        }
+        symbol
+    }
+
+    /// Send a whole frame through a frame mode link.
+    ///
+    /// Frames are serialised one after another, so a frame starts when the previous one has
+    /// been sent. Returns the simulation time at which the frame arrives at the other end.
+    pub fn send_frame(&self, frame: Vec<u8>) -> Result<Duration, TrySendError<Vec<u8>>> {
+        let octets = frame.len();
+        let overhead = match self.properties.mode {
+            LinkMode::Signal(_) => PREAMBLE.len() + 1,
+            _ => 0,
+        };
+        let start = clock::now().max(Duration::from_nanos(self.busy_until.load(Ordering::Relaxed)));
+        let end = start + self.properties.transmission_time(octets + overhead);
+        let transfer = Transfer {
+            frame,
+            arrival: end + self.properties.delay,
+        };
+        let arrival = transfer.arrival;
+        match self.frame_tx.try_send(transfer) {
+            Ok(()) => {
+                self.busy_until.store(end.as_nanos() as u64, Ordering::Relaxed);
+                self.statistics.frames_out.increment();
+                self.statistics.octets_out.add(octets as u64);
+                Ok(arrival)
+            }
+            Err(TrySendError::Full(transfer)) => {
+                self.statistics.overflows.add(octets as u64);
+                Err(TrySendError::Full(transfer.frame))
+            }
+            Err(TrySendError::Closed(transfer)) => Err(TrySendError::Closed(transfer.frame)),
+        }
+    }
+
+    /// Receive the next frame from a frame mode link, once it has fully arrived.
+    ///
+    /// On a signal mode link the frame is line coded and recovered by the receiver here,
+    /// frames it cannot recover are dropped.
+    pub fn recv_frame(&mut self) -> Result<Vec<u8>, TryRecvError> {
+        loop {
+            let transfer = match self.pending.take() {
+                Some(transfer) => transfer,
+                None => self.frame_rx.try_recv()?,
+            };
+            if transfer.arrival > clock::now() {
+                self.pending = Some(transfer);
+                return Err(TryRecvError::Empty);
+            }
+
+            let frame = match self.properties.mode {
+                LinkMode::Signal(coding) => match coding.receive(&coding.transmit(&transfer.frame)) {
+                    Ok(frame) => frame,
+                    Err(error) => {
+                        tracing::debug!(link = self.id, ?error, "frame lost on the line");
+                        self.statistics.coding_errors.increment();
+                        continue;
+                    }
+                },
+                _ => transfer.frame,
+            };
+            self.statistics.frames_in.increment();
+            self.statistics.octets_in.add(frame.len() as u64);
+            return Ok(frame);
+        }
+    }
+
+    pub fn statistics(&self) -> LinkCounters {
+        self.statistics.snapshot()
+    }
+
+    /// Whether a carrier is sensed: bytes are waiting or were just sent from the other end, or a frame is on its way
     pub fn is_recieving(&self) -> bool {
-        !self.rx.is_empty()
+        let carrier = Duration::from_nanos(self.carrier_in.load(Ordering::Relaxed));
+        !self.rx.is_empty() || clock::now() < carrier || self.pending.is_some() || !self.frame_rx.is_empty()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::line_coding::LineCode;
 
     #[tokio::test]
     async fn test_link() {
@@ -52,4 +308,74 @@ mod tests {
         assert_eq!(a.recv().is_err(), true);
         assert_eq!(b.recv().unwrap(), 42);
     }
+
+    #[tokio::test]
+    async fn test_link_up() {
+        let (a, b) = Link::connection();
+        assert_eq!(a.id(), b.id());
+        assert!(a.is_up());
+        drop(b);
+        assert!(!a.is_up());
+    }
+
+    #[tokio::test]
+    async fn test_extension() {
+        let (a, mut b) = Link::connection();
+        a.send_symbol(Symbol::Extension).unwrap();
+        a.send(42).unwrap();
+        assert!(b.is_recieving());
+        assert_eq!(b.recv_symbol(), Ok(Symbol::Extension));
+        assert_eq!(b.recv(), Ok(42));
+        assert_eq!(a.statistics().octets_out, 1);
+        assert_eq!(b.statistics().octets_in, 1);
+    }
+
+    #[tokio::test]
+    async fn test_link_statistics() {
+        let (a, mut b) = Link::connection();
+        for byte in 0..=2000 {
+            a.send(byte as u8).ok();
+        }
+        b.recv().ok();
+
+        assert_eq!(a.statistics().octets_out, 2000);
+        assert_eq!(a.statistics().overflows, 1);
+        assert_eq!(b.statistics().octets_in, 1);
+    }
+
+    #[tokio::test]
+    async fn test_frame_mode() {
+        let properties = LinkProperties::frames(800_000, Duration::ZERO);
+        assert_eq!(properties.transmission_time(100), Duration::from_millis(1));
+        assert_eq!(LinkProperties::frames(0, Duration::ZERO).transmission_time(1), Duration::from_nanos(u64::MAX));
+
+        let (a, mut b) = Link::connection_with(properties);
+        let first = a.send_frame(vec![1; 100]).unwrap();
+        let second = a.send_frame(vec![2; 100]).unwrap();
+        assert_eq!(second - first, Duration::from_millis(1));
+        assert!(b.is_recieving());
+        assert_eq!(b.recv_frame(), Err(TryRecvError::Empty));
+
+        tokio::time::sleep(second.saturating_sub(clock::now())).await;
+        assert_eq!(b.recv_frame().unwrap(), vec![1; 100]);
+        assert_eq!(b.recv_frame().unwrap(), vec![2; 100]);
+        assert_eq!(a.statistics().frames_out, 2);
+        assert_eq!(b.statistics().octets_in, 200);
+    }
+
+    #[tokio::test]
+    async fn test_signal_mode() {
+        let coding = LineCoding {
+            code: LineCode::Manchester,
+            clock_drift: 0,
+        };
+        let properties = LinkProperties {
+            mode: LinkMode::Signal(coding),
+            ..LinkProperties::frames(8_000_000, Duration::ZERO)
+        };
+        let (a, mut b) = Link::connection_with(properties);
+        let arrival = a.send_frame(vec![0x42; 46]).unwrap();
+        tokio::time::sleep(arrival.saturating_sub(clock::now())).await;
+        assert_eq!(b.recv_frame().unwrap(), vec![0x42; 46]);
+    }
 }
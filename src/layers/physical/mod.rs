@@ -1,5 +1,11 @@
+mod duplex;
+mod framing;
+mod line_coding;
 mod link;
 mod physical;
 
-pub use link::Link;
-pub use physical::PhysicalLayer;
+pub use duplex::{Duplex, DuplexSetting};
+pub use framing::{Framing, FramingError};
+pub use line_coding::PREAMBLE;
+pub use link::{Link, LinkMode, LinkProperties, Symbol};
+pub use physical::{attach, PhysicalLayer};
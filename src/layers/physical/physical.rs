@@ -1,24 +1,51 @@
-use super::Link;
-use crate::layers::NIC;
+use super::{Duplex, Link, LinkProperties, Symbol};
+use crate::layers::{PhysicalError, NIC};
+use crate::utils::clock;
 use std::sync::Arc;
 
 pub trait PhysicalLayer {
     fn nic(&self) -> &NIC;
 
-    fn connect(&self, other: Arc<impl PhysicalLayer>) {
-        let (one, two) = Link::connection();
-        self.nic().set_connection(Some(one));
-        other.nic().set_connection(Some(two));
+    /// The NIC a new link is attached to, devices with several ports return a free one
+    fn port(&self) -> Result<&NIC, PhysicalError> {
+        Ok(self.nic())
     }
 
-    async fn disconnect(&self) {
+    /// Connects the two NICs and auto-negotiates the duplex mode of each end
+    fn connect(&self, other: Arc<impl PhysicalLayer>) -> Result<(), PhysicalError> {
+        self.connect_with(other, LinkProperties::default())
+    }
+
+    /// Connects the two NICs through a link with the given properties
+    fn connect_with(&self, other: Arc<impl PhysicalLayer>, properties: LinkProperties) -> Result<(), PhysicalError> {
+        self.accepts(&properties)?;
+        other.accepts(&properties)?;
+        attach(self.port()?, other.port()?, properties)
+    }
+
+    /// Checks that a link with these properties can be attached, devices that relay between
+    /// their ports only take links of one mode
+    fn accepts(&self, _properties: &LinkProperties) -> Result<(), PhysicalError> {
+        Ok(())
+    }
+
+    async fn disconnect(&self) -> Result<(), PhysicalError> {
+        if !self.nic().is_connected() {
+            return Err(PhysicalError::LinkDown);
+        }
         self.nic().set_connection(None);
+        Ok(())
     }
 
     async fn transmit(&self, byte: u8) {
         self.nic().transmit(byte).await;
     }
 
+    /// Holds the carrier up for a byte time without sending data
+    async fn extend(&self) {
+        self.nic().transmit_symbol(Symbol::Extension).await;
+    }
+
     async fn receive(&self) -> Option<u8> {
         self.nic().recieve().await
     }
@@ -27,11 +54,45 @@ pub trait PhysicalLayer {
         self.nic().is_receiving()
     }
 
+    /// Reads the bytes that arrive while a carrier is sensed
+    async fn read_carrier(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        while self.carrier_sense() {
+            match self.receive().await {
+                Some(byte) => bytes.push(byte),
+                None => tokio::time::sleep(clock::BYTE_TIME).await,
+            }
+        }
+        bytes
+    }
+
     fn transmitting(&self) -> bool {
         self.nic().transmitting()
     }
 
+    /// A collision is a carrier sensed while transmitting, which cannot happen on a full duplex link
     fn collision_detect(&self) -> bool {
-        self.carrier_sense() && self.transmitting()
+        self.nic().duplex() == Duplex::Half && self.carrier_sense() && self.transmitting()
+    }
+}
+
+/// Links two NICs and auto-negotiates the duplex mode of each end
+pub fn attach(ours: &NIC, theirs: &NIC, properties: LinkProperties) -> Result<(), PhysicalError> {
+    if properties.bandwidth == 0 {
+        return Err(PhysicalError::ZeroBandwidth);
     }
+    let (one, two) = Link::connection_with(properties);
+    ours.set_duplex(ours.duplex_setting().resolve(&theirs.duplex_setting()));
+    theirs.set_duplex(theirs.duplex_setting().resolve(&ours.duplex_setting()));
+    ours.attach(one, theirs.mac());
+    theirs.attach(two, ours.mac());
+    tracing::debug!(
+        mac = %ours.mac(),
+        partner = %theirs.mac(),
+        duplex = ?ours.duplex(),
+        partner_duplex = ?theirs.duplex(),
+        mode = ?properties.mode,
+        "link up"
+    );
+    Ok(())
 }
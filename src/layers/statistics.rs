@@ -0,0 +1,126 @@
+/*
+  Reference:
+    RFC 2863 (The Interfaces Group MIB)
+    RFC 3635 (Definitions of Managed Objects for the Ethernet-like Interface Types)
+*/
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A monotonically increasing event counter that can be updated through a shared reference.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Declares a set of live counters and a plain snapshot of them that can be printed.
+macro_rules! statistics {
+    ($(#[$meta:meta])* $live:ident => $snapshot:ident { $($(#[$doc:meta])* $field:ident),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Default)]
+        pub struct $live {
+            $($(#[$doc])* pub $field: Counter,)*
+        }
+
+        impl $live {
+            pub fn snapshot(&self) -> $snapshot {
+                $snapshot {
+                    $($field: self.$field.get(),)*
+                }
+            }
+        }
+
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+        pub struct $snapshot {
+            $(pub $field: u64,)*
+        }
+
+        impl std::fmt::Display for $snapshot {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                $(writeln!(f, "{:<22}{}", stringify!($field), self.$field)?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+statistics! {
+    /// Counters of a NIC, updated by the MAC as frames are sent and received.
+    NicStatistics => NicCounters {
+        /// Frames passed to the MAC client
+        frames_in,
+        /// Octets of MAC client data received
+        octets_in,
+        /// Frames sent successfully
+        frames_out,
+        /// Octets of MAC client data sent
+        octets_out,
+        /// Collisions detected while sending, every attempt counts
+        collisions,
+        /// Collisions detected after the first slot time
+        late_collisions,
+        /// Frames dropped after too many collisions
+        excessive_collisions,
+        /// Frames dropped when no CTS or ACK came back within the retry limit
+        retry_limit_exceeded,
+        /// Frames received with a frame check sequence error
+        fcs_errors,
+        /// Frames received that are not a whole number of octets
+        alignment_errors,
+        /// Transmissions received in which no valid frame could be delimited
+        framing_errors,
+        /// Frames received that exceed the maximum frame size
+        frames_too_long,
+        /// Frames received shorter than the minimum frame size
+        runts,
+        /// Bytes that could not be sent because the link buffer was full
+        buffer_overflows,
+        /// Frames a device could not relay out of this port
+        frames_dropped,
+        /// LLC frames for a service access point no protocol is bound to
+        unknown_saps,
+    }
+}
+
+statistics! {
+    /// Counters of one end of a `Link`.
+    LinkStatistics => LinkCounters {
+        octets_out,
+        octets_in,
+        /// Frames carried by a frame mode link
+        frames_out,
+        frames_in,
+        /// Frames the receiver could not recover from the line coded signal
+        coding_errors,
+        /// Octets dropped because the receiving end did not keep up
+        overflows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot() {
+        let stats = NicStatistics::default();
+        stats.frames_out.increment();
+        stats.octets_out.add(46);
+
+        let counters = stats.snapshot();
+        assert_eq!(counters.frames_out, 1);
+        assert_eq!(counters.octets_out, 46);
+        assert_eq!(counters.collisions, 0);
+        assert!(counters.to_string().contains("octets_out            46"));
+    }
+}
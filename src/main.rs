@@ -1,6 +1,181 @@
 mod devices;
 mod layers;
+mod tui;
 mod utils;
 
-#[tokio::main]
-async fn main() {}
+use devices::{hub::Hub, ports::Ports, topology::Topology};
+use layers::{AccessControl, ErrorControl, MacAddr, PhysicalError, PhysicalLayer, ReceiveState, TransmitState, NIC};
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use utils::{
+    clock::{self, Clock, Pause, SimulationTime},
+    Simulateable,
+};
+
+/// Number of stations on the hub of the demo
+const DEMO_STATIONS: usize = 3;
+
+/// Byte times the demo runs for without the dashboard
+const DEMO_TIME: usize = 5000;
+
+/// An end station of the demo that sends a frame to its neighbour every now and then
+#[derive(Default)]
+struct Station {
+    nic: NIC,
+    transmit_state: Mutex<TransmitState>,
+    receive_state: Mutex<ReceiveState>,
+}
+
+impl PhysicalLayer for Station {
+    fn nic(&self) -> &NIC {
+        &self.nic
+    }
+}
+
+impl ErrorControl for Station {}
+
+impl AccessControl for Station {
+    async fn transmit_state(&self) -> MutexGuard<'_, TransmitState> {
+        self.transmit_state.lock().await
+    }
+
+    async fn receive_state(&self) -> MutexGuard<'_, ReceiveState> {
+        self.receive_state.lock().await
+    }
+}
+
+impl Station {
+    /// Runs the station's processes while the demo is not paused, sending to `dest`
+    fn spawn(self: &Arc<Self>, dest: MacAddr, pause: &Pause) {
+        let station = self.clone();
+        tokio::spawn(pause.gate(async move { station.byte_transmitter().await }));
+        let station = self.clone();
+        tokio::spawn(pause.gate(async move {
+            loop {
+                let _ = station.receive_frame().await;
+            }
+        }));
+        let station = self.clone();
+        tokio::spawn(pause.gate(async move {
+            loop {
+                let pause: usize = rand::random::<usize>() % 5000;
+                tokio::time::sleep(clock::byte_times(pause)).await;
+                let _ = station.transmit_frame(&dest, &station.mac(), 0x0800, vec![0x42; 100]).await;
+            }
+        }));
+    }
+}
+
+/// A hub with a few stations that send frames to their neighbours
+struct Demo {
+    hub: Arc<Hub>,
+    stations: Vec<Arc<Station>>,
+    pause: Pause,
+}
+
+impl Demo {
+    fn start() -> Result<Self, PhysicalError> {
+        let hub = Arc::new(Hub::default());
+        let stations: Vec<Arc<Station>> = (0..DEMO_STATIONS).map(|_| Arc::new(Station::default())).collect();
+        for station in stations.iter() {
+            station.connect(hub.clone())?;
+        }
+        let pause = Pause::default();
+        for (i, station) in stations.iter().enumerate() {
+            station.spawn(stations[(i + 1) % stations.len()].mac(), &pause);
+        }
+        Ok(Demo { hub, stations, pause })
+    }
+
+    fn topology(&self) -> Topology<'_> {
+        let mut topology = Topology::default();
+        topology.add(self.hub.name(), self.hub.interfaces());
+        for (i, station) in self.stations.iter().enumerate() {
+            topology.add(format!("station{i}"), [station.nic()]);
+        }
+        topology
+    }
+
+    /// Relays for `DEMO_TIME` byte times
+    async fn run(&self) {
+        for _ in 0..DEMO_TIME {
+            self.hub.tick().await;
+            tokio::time::sleep(clock::BYTE_TIME).await;
+        }
+    }
+}
+
+/// Prints the counters of every connected port and its link
+fn print_statistics(topology: &Topology) {
+    for (name, ports) in topology.nodes() {
+        for (port, nic) in ports.iter().enumerate().filter(|(_, nic)| nic.is_connected()) {
+            println!("{name} port {port} ({})", nic.mac());
+            print!("{}", nic.statistics().snapshot());
+            if let Some(link) = nic.link_statistics() {
+                println!("link");
+                print!("{link}");
+            }
+            println!();
+        }
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    // Every thread of the runtime reads the same simulation clock
+    let clock = Clock::start();
+    clock.install();
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .on_thread_start(move || clock.install())
+        .build()?
+        .block_on(run())
+}
+
+/// Runs the demo under the dashboard with `--tui`, or for a while without it with `--stats`,
+/// which prints the counters when the run ends. `--dot <file>` exports the topology to the file
+/// once the run has ended.
+async fn run() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let tui = args.iter().any(|arg| arg == "--tui");
+    let stats = args.iter().any(|arg| arg == "--stats");
+    let dot = args.iter().position(|arg| arg == "--dot").map(|i| args.get(i + 1));
+
+    // Filter with e.g. `RUST_LOG=network_simulator=trace` or by a NIC: `RUST_LOG=[nic{mac=..}]=trace`
+    let log = tui::EventLog::default();
+    if tui {
+        // Events go to the dashboard, printing them would garble the terminal
+        tracing_subscriber::registry()
+            .with(EnvFilter::from_default_env())
+            .with(log.clone())
+            .init();
+    } else {
+        tracing_subscriber::fmt()
+            .with_timer(SimulationTime)
+            .with_env_filter(EnvFilter::from_default_env())
+            .init();
+    }
+    if !(tui || stats || dot.is_some()) {
+        return Ok(());
+    }
+    let dot = match dot {
+        Some(Some(path)) => Some(path),
+        Some(None) => return Err(std::io::Error::other("--dot needs a file name")),
+        None => None,
+    };
+
+    let demo = Demo::start().map_err(std::io::Error::other)?;
+    let topology = demo.topology();
+    if tui {
+        tui::run(&topology, &log, &demo.pause, || demo.hub.tick()).await?;
+    } else if stats {
+        demo.run().await;
+    }
+    if stats {
+        print_statistics(&topology);
+    }
+    if let Some(path) = dot {
+        std::fs::write(path, topology.to_dot())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use super::EventLog;
+use crate::devices::topology::Topology;
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, List, Paragraph, Row, Table},
+    Frame,
+};
+use std::collections::BTreeMap;
+
+/// Width of a utilisation bar in cells
+const BAR_WIDTH: usize = 20;
+
+/// Weight of the latest tick in the smoothed link utilisation
+const SMOOTHING: f64 = 0.2;
+
+/// State of the simulation as shown by the terminal UI.
+#[derive(Default)]
+pub struct Dashboard {
+    pub ticks: u64,
+    pub paused: bool,
+    /// Octets sent over each link so far, by link id
+    octets: BTreeMap<usize, u64>,
+    /// Smoothed share of the link capacity used per tick, by link id
+    utilisation: BTreeMap<usize, f64>,
+}
+
+fn bar(fraction: f64) -> String {
+    let filled = (fraction.clamp(0.0, 1.0) * BAR_WIDTH as f64).round() as usize;
+    format!(
+        "{}{} {:>3.0}%",
+        "█".repeat(filled),
+        "░".repeat(BAR_WIDTH - filled),
+        fraction * 100.0
+    )
+}
+
+impl Dashboard {
+    /// Updates the link utilisation after a tick.
+    ///
+    /// Devices relay one byte per direction and tick, so a link is fully used when both ends
+    /// sent a byte in the tick.
+    pub fn sample(&mut self, topology: &Topology) {
+        for (id, endpoints) in topology.connections() {
+            let octets: u64 = endpoints
+                .iter()
+                .filter_map(|e| e.nic.link_statistics())
+                .map(|s| s.octets_out)
+                .sum();
+            // The sum drops when an end lets go of the link
+            let previous = self.octets.insert(id, octets).unwrap_or(octets);
+            let used = octets.saturating_sub(previous) as f64 / 2.0;
+            let smoothed = self.utilisation.entry(id).or_default();
+            *smoothed = (1.0 - SMOOTHING) * *smoothed + SMOOTHING * used.min(1.0);
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, topology: &Topology, log: &EventLog) {
+        let [header, body, events] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Percentage(60),
+            Constraint::Fill(1),
+        ])
+        .areas(frame.area());
+        let [links, devices] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(body);
+
+        let state = if self.paused { "Paused" } else { "Running" };
+        frame.render_widget(
+            Paragraph::new(format!(
+                "{state} | tick {} | [space] pause/resume  [s] step  [q] quit",
+                self.ticks
+            ))
+            .style(Style::default().add_modifier(Modifier::REVERSED)),
+            header,
+        );
+
+        let link_rows = topology.connections().into_iter().map(|(id, endpoints)| {
+            let ends: Vec<String> = endpoints.iter().map(|e| format!("{}:{}", e.node, e.port)).collect();
+            let state = if endpoints.iter().all(|e| e.up) { "up" } else { "down" };
+            let utilisation = self.utilisation.get(&id).copied().unwrap_or_default();
+            Row::new(vec![ends.join(" — "), state.to_string(), bar(utilisation)])
+        });
+        frame.render_widget(
+            Table::new(
+                link_rows,
+                [Constraint::Fill(1), Constraint::Length(4), Constraint::Length(BAR_WIDTH as u16 + 5)],
+            )
+            .header(Row::new(vec!["Link", "", "Utilisation"]).style(Style::default().add_modifier(Modifier::BOLD)))
+            .block(Block::default().borders(Borders::ALL).title("Topology")),
+            links,
+        );
+
+        let device_rows = topology.nodes().map(|(name, ports)| {
+            let connected = ports.iter().filter(|nic| nic.is_connected()).count();
+            let transmitting = ports.iter().filter(|nic| nic.transmitting()).count();
+            let counters = ports.iter().map(|nic| nic.statistics().snapshot());
+            let (frames_in, frames_out, collisions) = counters.fold((0, 0, 0), |acc, c| {
+                (acc.0 + c.frames_in, acc.1 + c.frames_out, acc.2 + c.collisions)
+            });
+            Row::new(vec![
+                name.to_string(),
+                format!("{connected}/{}", ports.len()),
+                if transmitting > 0 { "tx".to_string() } else { "idle".to_string() },
+                frames_in.to_string(),
+                frames_out.to_string(),
+                collisions.to_string(),
+            ])
+        });
+        frame.render_widget(
+            Table::new(
+                device_rows,
+                [
+                    Constraint::Fill(1),
+                    Constraint::Length(5),
+                    Constraint::Length(5),
+                    Constraint::Length(8),
+                    Constraint::Length(8),
+                    Constraint::Length(10),
+                ],
+            )
+            .header(
+                Row::new(vec!["Device", "Ports", "State", "In", "Out", "Collisions"])
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            )
+            .block(Block::default().borders(Borders::ALL).title("Devices")),
+            devices,
+        );
+
+        let lines = log.tail(events.height.saturating_sub(2) as usize);
+        frame.render_widget(
+            List::new(lines.into_iter().map(Line::from))
+                .block(Block::default().borders(Borders::ALL).title("Events")),
+            events,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::{hub::Hub, ports::Ports};
+    use crate::layers::{PhysicalLayer, NIC};
+    use crate::utils::Simulateable;
+    use ratatui::{backend::TestBackend, Terminal};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct TestDevice {
+        nic: NIC,
+    }
+
+    impl PhysicalLayer for TestDevice {
+        fn nic(&self) -> &NIC {
+            &self.nic
+        }
+    }
+
+    #[tokio::test]
+    async fn test_render() {
+        let hub = Arc::new(Hub::default());
+        let dev1 = Arc::new(TestDevice::default());
+        let dev2 = Arc::new(TestDevice::default());
+        dev1.connect(hub.clone()).unwrap();
+        dev2.connect(hub.clone()).unwrap();
+
+        let mut topology = Topology::default();
+        topology.add("hub", hub.interfaces());
+        topology.add("dev1", [dev1.nic()]);
+        topology.add("dev2", [dev2.nic()]);
+
+        let mut dashboard = Dashboard::default();
+        dashboard.sample(&topology);
+        dev1.transmit(0x09).await;
+        hub.tick().await;
+        dashboard.ticks += 1;
+        dashboard.sample(&topology);
+        assert!(dashboard.utilisation.values().any(|&u| u > 0.0));
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal
+            .draw(|frame| dashboard.render(frame, &topology, &EventLog::default()))
+            .unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("Running | tick 1"));
+        assert!(screen.contains("hub:0 — dev1:0"));
+        assert!(screen.contains("dev2"));
+    }
+
+    #[tokio::test]
+    async fn test_sample_link_down() {
+        let hub = Arc::new(Hub::default());
+        let dev = Arc::new(TestDevice::default());
+        dev.connect(hub.clone()).unwrap();
+
+        let mut topology = Topology::default();
+        topology.add("hub", hub.interfaces());
+        topology.add("dev", [dev.nic()]);
+
+        dev.transmit(0x09).await;
+        let mut dashboard = Dashboard::default();
+        dashboard.sample(&topology);
+        // The octets sent by the device no longer count once it lets go of the link
+        dev.disconnect().await.unwrap();
+        dashboard.sample(&topology);
+        assert!(dashboard.utilisation.values().all(|&u| u == 0.0));
+    }
+}
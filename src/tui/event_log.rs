@@ -0,0 +1,106 @@
+use crate::utils::clock;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Number of events kept for display
+const CAPACITY: usize = 1000;
+
+/// Collects the fields of a span or event as `name=value` pairs, the message first.
+#[derive(Default)]
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        if field.name() == "message" {
+            write!(self.0, "{:?}", value).unwrap();
+        } else {
+            write!(self.0, "{}={:?}", field.name(), value).unwrap();
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_debug(field, &format_args!("{}", value));
+    }
+}
+
+/// A tracing layer that keeps the most recent events as formatted lines.
+///
+/// Each line carries the simulation time, the level and the fields of the enclosing spans,
+/// e.g. the MAC address of the NIC. Clones share the same buffer.
+#[derive(Clone, Default)]
+pub struct EventLog {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl EventLog {
+    /// The last `n` lines, oldest first
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        let lines = self.lines.lock().unwrap();
+        lines.iter().skip(lines.len().saturating_sub(n)).cloned().collect()
+    }
+}
+
+impl<S> Layer<S> for EventLog
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut line = format!("{:>12?} {:>5} ", clock::now(), event.metadata().level());
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                match span.extensions().get::<Fields>() {
+                    Some(fields) => write!(line, "{}{{{}}}: ", span.name(), fields.0).unwrap(),
+                    None => write!(line, "{}: ", span.name()).unwrap(),
+                }
+            }
+        }
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        line.push_str(&fields.0);
+
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_event_log() {
+        let log = EventLog::default();
+        let subscriber = tracing_subscriber::registry().with(log.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("nic", mac = "00:11:22:33:44:55");
+            let _guard = span.enter();
+            tracing::warn!(octets = 64, "frame dropped");
+        });
+
+        let lines = log.tail(10);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with("WARN nic{mac=00:11:22:33:44:55}: frame dropped octets=64"));
+    }
+}
@@ -0,0 +1,99 @@
+mod dashboard;
+mod event_log;
+
+pub use dashboard::Dashboard;
+pub use event_log::EventLog;
+
+use crate::devices::topology::Topology;
+use crate::utils::clock::{Pause, BYTE_TIME};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use std::future::Future;
+use std::io;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+/// Time between redraws of the dashboard
+const FRAME_TIME: Duration = Duration::from_millis(50);
+
+/// Reads terminal events on a blocking thread, so the devices keep running while waiting for input.
+///
+/// The thread stops once the receiver is dropped.
+fn read_events() -> UnboundedReceiver<io::Result<Event>> {
+    let (tx, rx) = unbounded_channel();
+    tokio::task::spawn_blocking(move || loop {
+        let event = match event::poll(FRAME_TIME) {
+            Ok(true) => event::read(),
+            Ok(false) if tx.is_closed() => break,
+            Ok(false) => continue,
+            Err(e) => Err(e),
+        };
+        let failed = event.is_err();
+        if tx.send(event).is_err() || failed {
+            break;
+        }
+    });
+    rx
+}
+
+/// Runs the simulation under a terminal dashboard until `q` is pressed.
+///
+/// `tick` advances every device of the simulation by one step and is called once per byte time.
+/// While paused it is only called when stepping with `s`, and the processes gated by `pause` are
+/// held, so stations neither send nor time out. A step lets them run for a byte time.
+pub async fn run<F, Fut>(
+    topology: &Topology<'_>,
+    log: &EventLog,
+    pause: &Pause,
+    mut tick: F,
+) -> io::Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut terminal = ratatui::init();
+    let mut dashboard = Dashboard::default();
+    let mut events = read_events();
+    let mut redraw = interval(FRAME_TIME);
+    // Ticks missed while drawing are caught up, so devices relay a byte per byte time
+    let mut ticks = interval(BYTE_TIME);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+    let result = loop {
+        let mut step = false;
+        tokio::select! {
+            _ = redraw.tick() => {
+                if let Err(e) = terminal.draw(|frame| dashboard.render(frame, topology, log)) {
+                    break Err(e);
+                }
+            }
+            _ = ticks.tick(), if !dashboard.paused => step = true,
+            event = events.recv() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => break Ok(()),
+                    KeyCode::Char(' ') => {
+                        dashboard.paused = !dashboard.paused;
+                        pause.set(dashboard.paused);
+                        ticks.reset();
+                    }
+                    KeyCode::Char('s') | KeyCode::Char('n') => step = dashboard.paused,
+                    _ => {}
+                },
+                Some(Ok(_)) => {}
+                Some(Err(e)) => break Err(e),
+                None => break Ok(()),
+            },
+        }
+
+        if step {
+            if dashboard.paused {
+                pause.step(BYTE_TIME).await;
+            }
+            tick().await;
+            dashboard.ticks += 1;
+            dashboard.sample(topology);
+        }
+    };
+
+    ratatui::restore();
+    result
+}
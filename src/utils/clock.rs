@@ -0,0 +1,177 @@
+use std::cell::Cell;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::{Duration, Instant};
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
+
+/// Duration of a single byte time on the simulated medium.
+pub const BYTE_TIME: Duration = Duration::from_millis(1);
+
+/// A simulation clock, time is measured from its epoch.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    epoch: Instant,
+}
+
+impl Clock {
+    /// Starts a clock at the current time
+    pub fn start() -> Self {
+        Clock { epoch: Instant::now() }
+    }
+
+    /// Time elapsed since the clock was started
+    pub fn now(&self) -> Duration {
+        self.epoch.elapsed()
+    }
+
+    /// Makes this the clock `now` reads on the current thread
+    pub fn install(self) {
+        CLOCK.with(|clock| clock.set(Some(self)));
+    }
+}
+
+thread_local! {
+    static CLOCK: Cell<Option<Clock>> = const { Cell::new(None) };
+}
+
+/// Time elapsed on the simulation clock.
+///
+/// Reads the clock installed on the current thread, a thread without one starts its own the first
+/// time it reads the clock. Devices share a clock by installing the same one on every thread of the
+/// runtime, while each test gets its own.
+pub fn now() -> Duration {
+    CLOCK.with(|clock| {
+        let current = clock.get().unwrap_or_else(Clock::start);
+        clock.set(Some(current));
+        current.now()
+    })
+}
+
+/// Holds the processes of a simulation while it is paused.
+///
+/// A gated process is not polled while paused, so it neither sends nor times out until it is
+/// resumed. Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct Pause {
+    paused: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Pause {
+    fn default() -> Self {
+        Pause {
+            paused: Arc::new(watch::Sender::new(false)),
+        }
+    }
+}
+
+impl Pause {
+    pub fn set(&self, paused: bool) {
+        self.paused.send_replace(paused);
+    }
+
+    /// Lets the gated processes run for `duration` and pauses them again
+    pub async fn step(&self, duration: Duration) {
+        self.set(false);
+        tokio::time::sleep(duration).await;
+        self.set(true);
+    }
+
+    /// Runs `process` only while the simulation is not paused
+    pub fn gate<F: Future>(&self, process: F) -> impl Future<Output = F::Output> {
+        let mut paused = self.paused.subscribe();
+        async move {
+            tokio::pin!(process);
+            loop {
+                let _ = paused.wait_for(|&paused| !paused).await;
+                // A pause takes effect before the process gets another poll
+                tokio::select! {
+                    biased;
+                    _ = paused.wait_for(|&paused| paused) => {}
+                    output = &mut process => return output,
+                }
+            }
+        }
+    }
+}
+
+/// Timestamps log events with the simulation clock instead of the wall clock.
+pub struct SimulationTime;
+
+impl FormatTime for SimulationTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        write!(w, "{:>12?}", now())
+    }
+}
+
+/// Converts a number of byte times to a duration on the simulation clock.
+pub fn byte_times(n: usize) -> Duration {
+    BYTE_TIME * n as u32
+}
+
+/// Returns how long to wait from `now` until the start of the next slot.
+pub fn until_next_slot(now: Duration, slot: Duration) -> Duration {
+    let into_slot = now.as_nanos() % slot.as_nanos();
+    if into_slot == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_nanos((slot.as_nanos() - into_slot) as u64)
+}
+
+/// Sleeps until the next slot boundary of the simulation clock.
+pub async fn next_slot(slot: Duration) {
+    tokio::time::sleep(until_next_slot(now(), slot)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::{attach, LinkProperties, NIC};
+
+    #[test]
+    fn test_until_next_slot() {
+        let slot = byte_times(512);
+        assert_eq!(until_next_slot(Duration::ZERO, slot), Duration::ZERO);
+        assert_eq!(until_next_slot(byte_times(1024), slot), Duration::ZERO);
+        assert_eq!(until_next_slot(byte_times(100), slot), byte_times(412));
+        assert_eq!(until_next_slot(byte_times(513), slot), byte_times(511));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_install() {
+        let clock = Clock::start();
+        tokio::time::sleep(BYTE_TIME).await;
+        assert_eq!(now(), Duration::ZERO);
+        clock.install();
+        assert_eq!(now(), BYTE_TIME);
+        tokio::time::sleep(BYTE_TIME).await;
+        assert_eq!(now(), clock.now());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pause() {
+        let (one, two) = (Arc::new(NIC::default()), NIC::default());
+        attach(&one, &two, LinkProperties::default()).unwrap();
+        let pause = Pause::default();
+        pause.set(true);
+        let sender = one.clone();
+        tokio::spawn(pause.gate(async move {
+            loop {
+                sender.transmit(0x09).await;
+                tokio::time::sleep(BYTE_TIME).await;
+            }
+        }));
+
+        // Nobody reads the link, so the sender would overflow it if it ran while paused
+        tokio::time::sleep(byte_times(1000)).await;
+        let counters = one.link_statistics().unwrap();
+        assert_eq!((counters.octets_out, counters.overflows), (0, 0));
+
+        pause.step(byte_times(3)).await;
+        let octets = one.link_statistics().unwrap().octets_out;
+        assert!(octets > 0);
+        tokio::time::sleep(byte_times(1000)).await;
+        let counters = one.link_statistics().unwrap();
+        assert_eq!((counters.octets_out, counters.overflows), (octets, 0));
+    }
+}
@@ -1,3 +1,4 @@
+pub mod clock;
 mod crc;
 pub use crc::calculate_crc;
 
@@ -8,15 +9,6 @@ macro_rules! arc_mutex {
     };
 }
 
-#[macro_export]
-macro_rules! run_async {
-    ($data:expr) => {
-        tokio::task::block_in_place(|| {
-            tokio::runtime::Handle::current().block_on(async move { $data })
-        })
-    };
-}
-
 pub trait Simulateable {
     async fn tick(&self);
 }